@@ -1,61 +1,152 @@
-use crate::{Project, Task};
-use chrono::{Datelike, NaiveDate, NaiveDateTime, Weekday};
+use crate::{
+    forecast::{forecast, RemainingAnalysisResult, RemainingTask},
+    grouping::{group_tree, GroupKey, GroupTree, Grouping},
+    holiday::HolidayProvider,
+    holiday_work::{holiday_work, HolidayWork},
+    routine::{classify, routines, Routine},
+    working_hours::{working_hours, WorkingHoursAnalysisResult},
+    Project, Query, Task,
+};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike, Weekday};
 use itertools::Itertools;
 use serde::Serialize;
-use std::cmp::Ordering;
+use std::{cmp::Ordering, rc::Rc, str::FromStr};
 
 #[derive(Debug, Serialize)]
 pub struct AnalysisResult {
     pub project_name: String,
     pub value: Option<i64>,
     pub all: TasksAnalysisResult,
-    /// 平日・休日別
-    pub day: Vec<(String, TasksAnalysisResult)>,
-    /// グループ別
-    pub group: Vec<(String, TasksAnalysisResult)>,
+    /// 観点ごとの分類（既定は平日休日別・工程別・種類別）
+    pub groups: Vec<GroupTree>,
+    /// 工程別の分類（観点の指定によらず常に分類する、差分・比較で工程を突き合わせるのに使う）
+    #[serde(skip)]
+    pub by_group: GroupTree,
+    /// 同じ名前で繰り返し行ったタスク（集計したときのみ）
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub routines: Vec<Routine>,
+    /// 残タスク
+    pub remaining: RemainingAnalysisResult,
+    /// 日ごとの累積（バーンアップ）
+    pub burnup: BurnupAnalysisResult,
+    /// 時間帯・曜日別の作業時間
+    pub distribution: DistributionAnalysisResult,
+    /// 祝日・連休別
+    pub holiday_work: Vec<HolidayWork>,
+    /// 所定内外・法定外・深夜・休日の作業時間
+    pub working_hours: WorkingHoursAnalysisResult,
 }
 
+#[derive(Debug, Serialize)]
+pub struct DistributionAnalysisResult {
+    /// 時（0〜23）ごとの作業時間
+    pub hour: Vec<(u32, i64)>,
+    /// 曜日ごとの作業時間（月曜始まり）
+    pub weekday: Vec<(Weekday, i64)>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BurnupAnalysisResult {
+    /// 全体の見積時間（完了済みタスクと残タスクの見積時間の合計、残タスクがなければなし）
+    pub scope: Option<i64>,
+    pub days: Vec<BurnupDay>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BurnupDay {
+    pub date: NaiveDate,
+    /// 累積作業時間
+    pub work_time: i64,
+    /// 完了済みタスクの累積見積時間
+    pub estimated_time: i64,
+}
+
+/// 既定の設定でプロジェクトを分析する（設定を変えるときは [`Query`] を使う）
 pub fn analyze(tasks: Vec<Task>, project_id: &str, value: Option<i64>) -> Option<AnalysisResult> {
-    let target_tasks = Tasks(
-        tasks
-            .into_iter()
-            .filter(|t| {
-                t.project
-                    .as_ref()
-                    .map(|p| p.id == project_id)
-                    .unwrap_or(false)
-            })
-            .filter(|t| t.begin_time.and(t.end_time).is_some())
-            .map(From::from)
-            .sorted()
-            .collect(),
-        value,
-    );
-    let project_name = target_tasks.project_name(project_id)?;
+    Query::new().project(project_id).value(value).analyze(tasks)
+}
 
-    fn analyze_group(g: Vec<(String, Tasks)>) -> Vec<(String, TasksAnalysisResult)> {
-        g.into_iter().map(|(k, v)| (k, v.analyze())).collect()
-    }
+/// 完了済みタスクと残タスクを分析する（対象のタスクは絞り込み済み）
+pub(crate) fn analyze_tasks(
+    done: Vec<Task>,
+    remaining: Vec<Task>,
+    timeline: Timeline,
+    project_name: String,
+    project_id: Option<&str>,
+    query: &Query,
+    calendar: &dyn HolidayProvider,
+) -> AnalysisResult {
+    let value = query.value;
+    let mut tasks: Vec<_> = done.into_iter().map(From::from).sorted().collect();
+    classify(&mut tasks, &query.routine_rules);
+    let target_tasks = Tasks(tasks, value, query.session_gap, Rc::new(timeline));
 
-    let day = target_tasks.group_by(|t| match t.begin_time.weekday() {
-        Weekday::Sat | Weekday::Sun => "休日".into(),
-        _ => {
-            if t.holiday {
-                "休日".into()
-            } else {
-                "平日".into()
-            }
-        }
-    });
-    let group = target_tasks.group_by(|t| t.group.clone().unwrap_or("-".into()));
+    let groupings = if query.groupings.is_empty() {
+        Grouping::defaults()
+    } else {
+        query.groupings.clone()
+    };
+    let mut groups: Vec<_> = groupings
+        .iter()
+        .map(|g| group_tree(&target_tasks, g))
+        .collect();
+    let routines = if query.routines {
+        routines(&target_tasks.0)
+    } else {
+        vec![]
+    };
+    let by_group = group_tree(&target_tasks, &GroupKey::Group.into());
+    let burnup_days = target_tasks.burnup();
+    let distribution = target_tasks.distribution();
+    let mut holiday_work = holiday_work(&target_tasks.0, project_id, calendar);
+    let working_hours = working_hours(&target_tasks.0, project_id, &query.working_hours, calendar);
+    let mut all = target_tasks.analyze();
+    let mut remaining = forecast(remaining, &all, &by_group);
+    all.order_tasks(&query.task_order);
+    for g in groups.iter_mut() {
+        g.order_tasks(&query.task_order);
+    }
+    for h in holiday_work.iter_mut() {
+        query.task_order.apply(&mut h.tasks);
+    }
+    query.task_order.apply(&mut remaining.tasks);
+    let burnup = BurnupAnalysisResult {
+        scope: if remaining.tasks.is_empty() {
+            None
+        } else {
+            Some(all.total_estimated_time + remaining.total_estimated_time)
+        },
+        days: burnup_days,
+    };
 
-    Some(AnalysisResult {
+    AnalysisResult {
         project_name,
         value,
-        all: target_tasks.analyze(),
-        day: analyze_group(day),
-        group: analyze_group(group),
-    })
+        all,
+        groups,
+        by_group,
+        routines,
+        remaining,
+        burnup,
+        distribution,
+        holiday_work,
+        working_hours,
+    }
+}
+
+impl AnalysisResult {
+    /// 指定した観点の分類を返す（分析していなければなし）
+    pub fn tree(&self, grouping: &Grouping) -> Option<&GroupTree> {
+        self.groups.iter().find(|g| &g.grouping == grouping)
+    }
+}
+
+/// タスク名の最初の単語を工程名とみなす（単語が1つしかない場合はなし）
+pub(crate) fn group_name(name: &str) -> Option<String> {
+    name.split_whitespace()
+        .tuple_combinations::<(_, _)>()
+        .next()
+        .map(|(a, _)| a.into())
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -74,6 +165,10 @@ pub struct AnalysisResultTask {
     pub end_time: NaiveDateTime,
     pub timespan: i64,
     pub holiday: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub holiday_name: Option<String>,
+    /// 定型タスクかどうか
+    pub routine: bool,
 }
 
 impl PartialEq for AnalysisResultTask {
@@ -92,7 +187,7 @@ impl Ord for AnalysisResultTask {
 
 impl PartialOrd for AnalysisResultTask {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.begin_time.cmp(&other.begin_time))
+        Some(self.cmp(other))
     }
 }
 
@@ -100,32 +195,201 @@ impl From<Task> for AnalysisResultTask {
     fn from(task: Task) -> Self {
         let begin_time = task.begin_time.unwrap();
         let end_time = task.end_time.unwrap();
+        let estimated_time = task.estimated_time.filter(|t| t.num_minutes() != 0);
 
         Self {
             id: task.id,
             name: task.name.clone(),
-            group: task
-                .name
-                .split_whitespace()
-                .tuple_combinations::<(_, _)>()
-                .next()
-                .map(|(a, _)| a.into()),
+            group: group_name(&task.name),
             project: task.project,
             comment: task.comment,
-            estimated_time: task.estimated_time.map(|t| t.num_minutes()),
-            time_gap_ratio: task
-                .estimated_time
+            estimated_time: estimated_time.map(|t| t.num_minutes()),
+            time_gap_ratio: estimated_time
                 .map(|e| (end_time - begin_time).num_minutes() as f64 / e.num_minutes() as f64),
             begin_time: task.begin_time.unwrap(),
             end_time: task.end_time.unwrap(),
             timespan: (task.end_time.unwrap() - task.begin_time.unwrap()).num_minutes(),
             holiday: task.holiday,
+            holiday_name: task.holiday_name,
+            routine: false,
+        }
+    }
+}
+
+/// タスクの一覧を並べ替える項目
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// 所要時間
+    Timespan,
+    /// 実績/予定
+    Gap,
+    /// 見積時間
+    Estimated,
+    /// タスク名
+    Name,
+    /// 開始時刻
+    Date,
+}
+
+impl FromStr for SortKey {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "timespan" => Ok(Self::Timespan),
+            "gap" => Ok(Self::Gap),
+            "estimated" => Ok(Self::Estimated),
+            "name" => Ok(Self::Name),
+            "date" => Ok(Self::Date),
+            _ => Err("invalid sort key"),
         }
     }
 }
 
+/// タスクの一覧の並び順
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaskOrder {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+impl Default for TaskOrder {
+    fn default() -> Self {
+        Self {
+            key: SortKey::Date,
+            descending: false,
+        }
+    }
+}
+
+/// 並べ替えに使うタスクの値
+pub(crate) trait OrderedTask {
+    fn timespan(&self) -> Option<i64>;
+    fn gap(&self) -> Option<f64>;
+    fn estimated(&self) -> Option<i64>;
+    fn name(&self) -> &str;
+    fn begin_time(&self) -> Option<NaiveDateTime>;
+}
+
+impl OrderedTask for AnalysisResultTask {
+    fn timespan(&self) -> Option<i64> {
+        Some(self.timespan)
+    }
+    fn gap(&self) -> Option<f64> {
+        self.time_gap_ratio
+    }
+    fn estimated(&self) -> Option<i64> {
+        self.estimated_time
+    }
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn begin_time(&self) -> Option<NaiveDateTime> {
+        Some(self.begin_time)
+    }
+}
+
+/// 残タスクは予測所要時間を所要時間とみなし、開始時刻では並べ替えない（実行予定の順のまま）
+impl OrderedTask for RemainingTask {
+    fn timespan(&self) -> Option<i64> {
+        self.forecast_time
+    }
+    fn gap(&self) -> Option<f64> {
+        self.time_gap_ratio
+    }
+    fn estimated(&self) -> Option<i64> {
+        self.estimated_time
+    }
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn begin_time(&self) -> Option<NaiveDateTime> {
+        None
+    }
+}
+
+impl TaskOrder {
+    /// 並べ替える（値のないタスクは向きによらず最後）
+    pub(crate) fn apply<T: OrderedTask>(&self, tasks: &mut [T]) {
+        fn last<T: PartialOrd>(a: Option<T>, b: Option<T>, descending: bool) -> Ordering {
+            match (a, b) {
+                (Some(a), Some(b)) => {
+                    let o = a.partial_cmp(&b).unwrap_or(Ordering::Equal);
+                    if descending {
+                        o.reverse()
+                    } else {
+                        o
+                    }
+                }
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        }
+
+        let d = self.descending;
+        tasks.sort_by(|a, b| match self.key {
+            SortKey::Timespan => last(a.timespan(), b.timespan(), d),
+            SortKey::Gap => last(a.gap(), b.gap(), d),
+            SortKey::Estimated => last(a.estimated(), b.estimated(), d),
+            SortKey::Name => last(Some(a.name()), Some(b.name()), d),
+            SortKey::Date => last(a.begin_time(), b.begin_time(), d),
+        });
+    }
+}
+
 #[derive(Debug, Serialize)]
-struct Tasks(Vec<AnalysisResultTask>, Option<i64>);
+pub(crate) struct Tasks(
+    pub(crate) Vec<AnalysisResultTask>,
+    pub(crate) Option<i64>,
+    pub(crate) i64,
+    #[serde(skip)] pub(crate) Rc<Timeline>,
+);
+
+/// 絞り込む前の完了済みタスクの時間帯（空き時間とプロジェクトの切り替えは、分析しないタスクも含めて数える）
+#[derive(Debug, Default)]
+pub(crate) struct Timeline(Vec<(NaiveDateTime, NaiveDateTime, Option<String>)>);
+
+impl Timeline {
+    pub(crate) fn new(tasks: &[Task]) -> Self {
+        Self(
+            tasks
+                .iter()
+                .filter_map(|t| {
+                    Some((
+                        t.begin_time?,
+                        t.end_time?,
+                        t.project.as_ref().map(|p| p.id.clone()),
+                    ))
+                })
+                .sorted()
+                .collect(),
+        )
+    }
+
+    /// 間の時間のうち、どのタスクもしていない時間
+    fn idle_time(&self, begin: NaiveDateTime, end: NaiveDateTime) -> i64 {
+        let mut idle = 0;
+        let mut last = begin;
+        for (b, e, _) in self.0.iter().filter(|(b, e, _)| *b < end && *e > begin) {
+            if *b > last {
+                idle += (*b - last).num_minutes();
+            }
+            last = last.max(*e);
+        }
+        idle + (end - last).num_minutes().max(0)
+    }
+
+    /// その日の連続するタスク間でプロジェクトが切り替わった回数
+    fn project_switches(&self, date: NaiveDate) -> i64 {
+        self.0
+            .iter()
+            .filter(|(b, _, _)| b.date() == date)
+            .tuple_windows()
+            .filter(|(a, b)| a.2 != b.2)
+            .count() as i64
+    }
+}
 
 #[derive(Debug, Serialize)]
 pub struct TasksAnalysisResult {
@@ -149,31 +413,59 @@ pub struct TasksAnalysisResult {
     pub work_time_per_day_deviation: f64,
     /// 1ページあたりの作業時間（ページ数といったパラメータを外から差し込む）
     pub work_time_per_value: Option<f64>,
+    /// セッション数（短い間隔で続くタスクをまとめたもの）
+    pub sessions: i64,
+    /// セッションあたり時間平均
+    pub session_time_avg: f64,
+    /// セッションあたり時間最大
+    pub session_time_max: i64,
+    /// セッション間でどのタスクもしていない時間の合計（分析しないタスクをしていた時間は除く）
+    pub idle_time: i64,
+    /// 同じ日の連続するタスク間で工程が切り替わった回数（分析するタスクだけで数える）
+    pub group_switches: i64,
+    /// 作業した日の連続するタスク間でプロジェクトが切り替わった回数（その日のすべてのタスクで数える）
+    pub project_switches: i64,
+    /// 日ごとのセッション・切り替え
+    pub fragmentation_per_days: Vec<FragmentationDay>,
     /// 作業別（タスクごとの所要時間を並べる）
     pub tasks: Vec<AnalysisResultTask>,
 }
 
+impl TasksAnalysisResult {
+    /// タスクの一覧を並べ替える（集計した値は変わらない）
+    pub(crate) fn order_tasks(&mut self, order: &TaskOrder) {
+        order.apply(&mut self.tasks);
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FragmentationDay {
+    pub date: NaiveDate,
+    pub sessions: i64,
+    pub session_time_avg: f64,
+    pub session_time_max: i64,
+    pub idle_time: i64,
+    pub group_switches: i64,
+    pub project_switches: i64,
+}
+
 impl Tasks {
-    fn group_by<F: Fn(&&AnalysisResultTask) -> String>(&self, key: F) -> Vec<(String, Self)> {
+    pub(crate) fn group_by<F: Fn(&&AnalysisResultTask) -> String>(
+        &self,
+        key: F,
+    ) -> Vec<(String, Self)> {
         self.0
             .iter()
             .sorted_by_key(|a| key(a))
             .group_by::<String, _>(|a| key(a))
             .into_iter()
-            .map(|(k, v)| (k, Self(v.cloned().collect(), self.1)))
-            .collect()
-    }
-
-    fn project_name(&self, project_id: &str) -> Option<String> {
-        self.0
-            .iter()
-            .find(|t| {
-                t.project
-                    .as_ref()
-                    .map(|p| p.id == project_id)
-                    .unwrap_or(false)
+            .map(|(k, v)| {
+                (
+                    k,
+                    Self(v.cloned().collect(), self.1, self.2, self.3.clone()),
+                )
             })
-            .map(|p| p.project.as_ref().unwrap().name.to_string())
+            .collect()
     }
 
     fn total_estimated_time(&self) -> i64 {
@@ -203,18 +495,16 @@ impl Tasks {
     fn work_time_per_day_max(&self) -> i64 {
         self.work_time_per_days()
             .iter()
-            .map(|(_, v)| v)
+            .map(|(_, v)| *v)
             .max()
-            .map(|v| *v)
             .unwrap_or(0)
     }
 
     fn work_time_per_day_min(&self) -> i64 {
         self.work_time_per_days()
             .iter()
-            .map(|(_, v)| v)
+            .map(|(_, v)| *v)
             .min()
-            .map(|v| *v)
             .unwrap_or(0)
     }
 
@@ -225,7 +515,7 @@ impl Tasks {
             .map(|(_, v)| *v)
             .sorted()
             .collect();
-        v.get(v.len() / 2).map(|v| *v).unwrap_or(0)
+        v.get(v.len() / 2).copied().unwrap_or(0)
     }
 
     fn work_time_per_day_deviation(&self) -> f64 {
@@ -249,18 +539,119 @@ impl Tasks {
     }
 
     fn work_time_per_days(&self) -> Vec<(NaiveDate, i64)> {
+        self.sum_per_days(|t| t.timespan)
+    }
+
+    fn estimated_time_per_days(&self) -> Vec<(NaiveDate, i64)> {
+        self.sum_per_days(|t| t.estimated_time.unwrap_or(0))
+    }
+
+    fn sum_per_days<F: Fn(&AnalysisResultTask) -> i64>(&self, f: F) -> Vec<(NaiveDate, i64)> {
         self.0
             .iter()
             .sorted_by_key(|a| a.begin_time.date())
             .group_by(|a| a.begin_time.date())
             .into_iter()
-            .map(|(k, v)| (k, v.map(|t| t.timespan).sum()))
+            .map(|(k, v)| (k, v.map(&f).sum()))
             .collect()
     }
 
-    fn analyze(self) -> TasksAnalysisResult {
+    fn fragmentation_per_days(&self) -> Vec<FragmentationDay> {
+        self.0
+            .iter()
+            .sorted()
+            .group_by(|t| t.begin_time.date())
+            .into_iter()
+            .map(|(date, tasks)| {
+                let tasks: Vec<_> = tasks.collect();
+                let mut sessions: Vec<(NaiveDateTime, NaiveDateTime)> = vec![];
+                let mut idle_time = 0;
+
+                for t in tasks.iter() {
+                    match sessions.last_mut() {
+                        Some((_, end)) if (t.begin_time - *end).num_minutes() < self.2 => {
+                            *end = t.end_time.max(*end);
+                        }
+                        Some((_, end)) => {
+                            idle_time += self.3.idle_time(*end, t.begin_time);
+                            sessions.push((t.begin_time, t.end_time));
+                        }
+                        None => sessions.push((t.begin_time, t.end_time)),
+                    }
+                }
+
+                let lengths: Vec<_> = sessions
+                    .iter()
+                    .map(|(b, e)| (*e - *b).num_minutes())
+                    .collect();
+
+                FragmentationDay {
+                    date,
+                    sessions: lengths.len() as i64,
+                    session_time_avg: lengths.iter().sum::<i64>() as f64 / lengths.len() as f64,
+                    session_time_max: lengths.iter().max().copied().unwrap_or(0),
+                    idle_time,
+                    group_switches: tasks
+                        .iter()
+                        .tuple_windows()
+                        .filter(|(a, b)| a.group != b.group)
+                        .count() as i64,
+                    project_switches: self.3.project_switches(date),
+                }
+            })
+            .collect()
+    }
+
+    /// 時の境界でタスクを分割して、時間帯・曜日ごとに作業時間を集計する
+    fn distribution(&self) -> DistributionAnalysisResult {
+        let mut hour = [0i64; 24];
+        let mut weekday = [0i64; 7];
+
+        for t in self.0.iter() {
+            let mut begin = t.begin_time;
+            while begin < t.end_time {
+                let next =
+                    (begin.date().and_hms(begin.hour(), 0, 0) + Duration::hours(1)).min(t.end_time);
+                let minutes = (next - begin).num_minutes();
+                hour[begin.hour() as usize] += minutes;
+                weekday[begin.weekday().num_days_from_monday() as usize] += minutes;
+                begin = next;
+            }
+        }
+
+        DistributionAnalysisResult {
+            hour: (0..24).zip(hour.iter().copied()).collect(),
+            weekday: (0..7)
+                .scan(Weekday::Sun, |d, _| {
+                    *d = d.succ();
+                    Some(*d)
+                })
+                .zip(weekday.iter().copied())
+                .collect(),
+        }
+    }
+
+    fn burnup(&self) -> Vec<BurnupDay> {
+        self.work_time_per_days()
+            .into_iter()
+            .zip(self.estimated_time_per_days())
+            .scan((0, 0), |(w, e), ((date, dw), (_, de))| {
+                *w += dw;
+                *e += de;
+                Some(BurnupDay {
+                    date,
+                    work_time: *w,
+                    estimated_time: *e,
+                })
+            })
+            .collect()
+    }
+
+    pub(crate) fn analyze(self) -> TasksAnalysisResult {
         let tw = self.total_work_time();
         let te = self.total_estimated_time();
+        let fragmentation = self.fragmentation_per_days();
+        let sessions = fragmentation.iter().map(|d| d.sessions).sum::<i64>();
 
         TasksAnalysisResult {
             total_estimated_time: te,
@@ -277,7 +668,166 @@ impl Tasks {
             work_time_per_day_median: self.work_time_per_day_median(),
             work_time_per_day_deviation: self.work_time_per_day_deviation(),
             work_time_per_value: self.work_time_per_value(),
+            sessions,
+            session_time_avg: fragmentation
+                .iter()
+                .map(|d| d.session_time_avg * d.sessions as f64)
+                .sum::<f64>()
+                / sessions as f64,
+            session_time_max: fragmentation
+                .iter()
+                .map(|d| d.session_time_max)
+                .max()
+                .unwrap_or(0),
+            idle_time: fragmentation.iter().map(|d| d.idle_time).sum(),
+            group_switches: fragmentation.iter().map(|d| d.group_switches).sum(),
+            project_switches: fragmentation.iter().map(|d| d.project_switches).sum(),
+            fragmentation_per_days: fragmentation,
             tasks: self.tasks(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{analyze, at, task};
+
+    #[test]
+    fn tasks_with_small_gaps_form_one_session() {
+        let tasks = vec![
+            task("1", "執筆 1章")
+                .project("a", "A")
+                .time(at(1, 9, 0), at(1, 9, 30))
+                .build(),
+            task("2", "執筆 2章")
+                .project("a", "A")
+                .time(at(1, 9, 33), at(1, 10, 0))
+                .build(),
+            task("3", "校正 1章")
+                .project("a", "A")
+                .time(at(1, 10, 30), at(1, 11, 0))
+                .build(),
+        ];
+        let all = analyze(tasks, "a", None).unwrap().all;
+        assert_eq!(all.sessions, 2);
+        assert_eq!(all.session_time_max, 60);
+        assert_eq!(all.session_time_avg, 45.0);
+        assert_eq!(all.idle_time, 30);
+        assert_eq!(all.group_switches, 1);
+    }
+
+    #[test]
+    fn overlapping_tasks_extend_the_session() {
+        let tasks = vec![
+            task("1", "1")
+                .project("a", "A")
+                .time(at(1, 9, 0), at(1, 10, 0))
+                .build(),
+            task("2", "2")
+                .project("a", "A")
+                .time(at(1, 9, 30), at(1, 9, 45))
+                .build(),
+            task("3", "3")
+                .project("a", "A")
+                .time(at(1, 9, 50), at(1, 10, 30))
+                .build(),
+        ];
+        let all = analyze(tasks, "a", None).unwrap().all;
+        assert_eq!(all.sessions, 1);
+        assert_eq!(all.session_time_max, 90);
+        assert_eq!(all.idle_time, 0);
+    }
+
+    #[test]
+    fn other_projects_are_not_idle_time_and_count_as_switches() {
+        let tasks = vec![
+            task("1", "執筆 1章")
+                .project("a", "A")
+                .time(at(1, 9, 0), at(1, 10, 0))
+                .build(),
+            task("2", "会議")
+                .project("b", "B")
+                .time(at(1, 10, 0), at(1, 10, 30))
+                .build(),
+            task("3", "執筆 2章")
+                .project("a", "A")
+                .time(at(1, 11, 0), at(1, 12, 0))
+                .build(),
+            task("4", "会議")
+                .project("b", "B")
+                .time(at(2, 9, 0), at(2, 10, 0))
+                .build(),
+        ];
+        let all = analyze(tasks, "a", None).unwrap().all;
+        assert_eq!(all.sessions, 2);
+        assert_eq!(all.idle_time, 30);
+        assert_eq!(all.group_switches, 0);
+        assert_eq!(all.project_switches, 2);
+        assert_eq!(all.fragmentation_per_days.len(), 1);
+    }
+
+    #[test]
+    fn burnup_accumulates_per_day() {
+        let done = |id: &str, d: u32, minutes: u32, estimated: i64| {
+            task(id, id)
+                .project("a", "A")
+                .estimated(estimated)
+                .time(at(d, 9, 0), at(d, 9 + minutes / 60, minutes % 60))
+                .build()
+        };
+        let tasks = vec![
+            done("1", 1, 30, 20),
+            done("2", 2, 40, 30),
+            done("3", 2, 20, 30),
+            done("4", 4, 15, 10),
+        ];
+        let days = |res: &AnalysisResult| {
+            res.burnup
+                .days
+                .iter()
+                .map(|d| (d.date.day(), d.work_time, d.estimated_time))
+                .collect::<Vec<_>>()
+        };
+
+        let res = analyze(tasks.clone(), "a", None).unwrap();
+        assert_eq!(days(&res), vec![(1, 30, 20), (2, 90, 80), (4, 105, 90)]);
+        assert_eq!(res.burnup.scope, None);
+
+        let mut tasks = tasks;
+        tasks.push(task("5", "5").project("a", "A").estimated(60).build());
+        let res = analyze(tasks, "a", None).unwrap();
+        assert_eq!(days(&res).last(), Some(&(4, 105, 90)));
+        assert_eq!(res.burnup.scope, Some(150));
+    }
+
+    #[test]
+    fn distribution_splits_tasks_at_hour_boundaries() {
+        let tasks = vec![
+            task("1", "1")
+                .project("a", "A")
+                .time(at(1, 9, 40), at(1, 11, 10))
+                .build(),
+            task("2", "2")
+                .project("a", "A")
+                .time(at(7, 23, 30), at(8, 0, 20))
+                .build(),
+        ];
+        let distribution = analyze(tasks, "a", None).unwrap().distribution;
+
+        let hours: Vec<_> = distribution
+            .hour
+            .iter()
+            .copied()
+            .filter(|(_, m)| *m > 0)
+            .collect();
+        assert_eq!(hours, vec![(0, 20), (9, 20), (10, 60), (11, 10), (23, 30)]);
+        assert_eq!(distribution.hour.len(), 24);
+        assert_eq!(distribution.weekday[0], (Weekday::Mon, 110));
+        assert_eq!(distribution.weekday[6], (Weekday::Sun, 30));
+        assert_eq!(
+            distribution.weekday.iter().map(|(_, m)| m).sum::<i64>(),
+            140
+        );
+    }
+}
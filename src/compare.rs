@@ -0,0 +1,143 @@
+use crate::{analyzer::TasksAnalysisResult, AnalysisResult};
+use itertools::Itertools;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct CompareResult {
+    pub projects: Vec<CompareProject>,
+    /// 工程別（工程名で突き合わせ、プロジェクトの順に並べる。工程がないプロジェクトはなし）
+    pub group: Vec<(String, Vec<Option<CompareMetrics>>)>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CompareProject {
+    pub project_name: String,
+    pub value: Option<i64>,
+    pub all: CompareMetrics,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct CompareMetrics {
+    /// 合計見積時間
+    pub total_estimated_time: i64,
+    /// 合計作業時間
+    pub total_work_time: i64,
+    /// 合計見積時間と合計所要時間の倍率
+    pub total_time_gap_ratio: Option<f64>,
+    /// 稼働日数
+    pub work_days: i64,
+    /// 1日あたり作業時間平均
+    pub work_time_per_day: f64,
+    /// 1ページあたりの作業時間
+    pub work_time_per_value: Option<f64>,
+}
+
+impl From<&TasksAnalysisResult> for CompareMetrics {
+    fn from(r: &TasksAnalysisResult) -> Self {
+        Self {
+            total_estimated_time: r.total_estimated_time,
+            total_work_time: r.total_work_time,
+            total_time_gap_ratio: r.total_time_gap_ratio,
+            work_days: r.work_days,
+            work_time_per_day: r.work_time_per_day,
+            work_time_per_value: r.work_time_per_value,
+        }
+    }
+}
+
+/// 複数のプロジェクトの分析結果の主な指標を並べる
+pub fn compare(results: &[AnalysisResult]) -> CompareResult {
+    let group = results
+        .iter()
+        .flat_map(|r| r.by_group.nodes.iter().map(|n| &n.key))
+        .unique()
+        .sorted()
+        .map(|k| {
+            (
+                k.clone(),
+                results
+                    .iter()
+                    .map(|r| r.by_group.find(k).map(|v| v.into()))
+                    .collect(),
+            )
+        })
+        .collect();
+
+    CompareResult {
+        projects: results
+            .iter()
+            .map(|r| CompareProject {
+                project_name: r.project_name.clone(),
+                value: r.value,
+                all: (&r.all).into(),
+            })
+            .collect(),
+        group,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{analyze, at, task};
+
+    #[test]
+    fn projects_are_side_by_side_and_groups_matched_by_name() {
+        let tasks = vec![
+            task("1", "執筆 1章")
+                .project("a", "本A")
+                .time(at(1, 9, 0), at(1, 10, 0))
+                .build(),
+            task("2", "校正 1章")
+                .project("a", "本A")
+                .time(at(2, 9, 0), at(2, 9, 30))
+                .build(),
+            task("3", "執筆 1章")
+                .project("b", "本B")
+                .time(at(1, 13, 0), at(1, 13, 45))
+                .build(),
+        ];
+        let results = vec![
+            analyze(tasks.clone(), "a", Some(100)).unwrap(),
+            analyze(tasks, "b", Some(50)).unwrap(),
+        ];
+        let res = compare(&results);
+
+        let projects: Vec<_> = res
+            .projects
+            .iter()
+            .map(|p| {
+                (
+                    p.project_name.as_str(),
+                    p.all.total_work_time,
+                    p.all.work_days,
+                    p.all.work_time_per_value,
+                )
+            })
+            .collect();
+        assert_eq!(
+            projects,
+            vec![("本A", 90, 2, Some(0.9)), ("本B", 45, 1, Some(0.9))]
+        );
+
+        let groups: Vec<_> = res
+            .group
+            .iter()
+            .map(|(k, v)| {
+                (
+                    k.as_str(),
+                    v.iter()
+                        .map(|m| m.as_ref().map(|m| m.total_work_time))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            groups,
+            vec![
+                ("執筆", vec![Some(60), Some(45)]),
+                ("校正", vec![Some(30), None])
+            ]
+        );
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{holiday::HOLIDAYS, Project, Task};
+use crate::{
+    holiday::{HolidayCalendar, HolidayProvider},
+    Project, Task,
+};
 use chrono::{Duration, NaiveDate, NaiveTime};
 use encoding_rs_io::DecodeReaderBytes;
 use serde::Deserialize;
@@ -16,6 +19,7 @@ struct TccTask {
     #[serde(rename = "見積時間")]
     estimated_time: Option<String>,
     #[serde(rename = "実績時間")]
+    #[allow(dead_code)]
     used_time: Option<String>,
     #[serde(rename = "開始時間")]
     begin_time: Option<String>,
@@ -37,18 +41,17 @@ impl TryInto<Task> for TccTask {
         let estimated_time = self
             .estimated_time
             .as_ref()
-            .and_then(|t| NaiveTime::parse_from_str(&t, "%H:%M").ok())
-            .map(|t| t - NaiveTime::from_hms(0, 0, 0))
-            .and_then(|t| if t.num_minutes() == 0 { None } else { Some(t) });
+            .and_then(|t| NaiveTime::parse_from_str(t, "%H:%M").ok())
+            .map(|t| t - NaiveTime::from_hms(0, 0, 0));
         let begin_time = self
             .begin_time
             .as_ref()
-            .and_then(|t| NaiveTime::parse_from_str(&t, "%H:%M").ok())
+            .and_then(|t| NaiveTime::parse_from_str(t, "%H:%M").ok())
             .map(|t| date.and_time(t));
         let end_time = self
             .end_time
             .as_ref()
-            .and_then(|t| NaiveTime::parse_from_str(&t, "%H:%M").ok())
+            .and_then(|t| NaiveTime::parse_from_str(t, "%H:%M").ok())
             .map(|t| {
                 date.and_time(t)
                     + Duration::days(
@@ -66,6 +69,8 @@ impl TryInto<Task> for TccTask {
         Ok(Task {
             id: self.id,
             name: self.name.to_string(),
+            row: 0,
+            date,
             estimated_time,
             begin_time,
             end_time,
@@ -74,20 +79,50 @@ impl TryInto<Task> for TccTask {
                 name: n.to_string(),
                 id: p.to_string(),
             }),
-            holiday: begin_time
-                .map(|d| HOLIDAYS.contains_key(&d.date()))
-                .unwrap_or(false),
+            holiday: false,
+            holiday_name: None,
         })
     }
 }
 
 pub fn parse(r: impl Read) -> Vec<Task> {
-    csv::ReaderBuilder::new()
+    parse_with(r, &HolidayCalendar::builtin())
+}
+
+/// 指定したカレンダーで休日を判定しながら読み込む
+pub fn parse_with(r: impl Read, calendar: &impl HolidayProvider) -> Vec<Task> {
+    let mut reader = csv::ReaderBuilder::new()
         .delimiter(b'\t')
         .has_headers(true)
-        .from_reader(DecodeReaderBytes::new(r))
-        .deserialize::<TccTask>()
-        .into_iter()
-        .filter_map(|r| r.ok().and_then(|t| t.try_into().ok()))
-        .collect()
+        .from_reader(DecodeReaderBytes::new(r));
+    let headers = match reader.headers() {
+        Ok(h) => h.clone(),
+        Err(_) => return vec![],
+    };
+
+    let mut tasks: Vec<Task> = reader
+        .records()
+        .filter_map(|r| {
+            let r = r.ok()?;
+            let t: TccTask = r.deserialize(Some(&headers)).ok()?;
+            let mut task: Task = t.try_into().ok()?;
+            task.row = r.position().map(|p| p.line()).unwrap_or(0);
+            Some(task)
+        })
+        .collect();
+    resolve_holidays(&mut tasks, calendar);
+    tasks
+}
+
+/// タスクの休日（`holiday`・`holiday_name`）を、いまのプロジェクトのカレンダーで判定し直す
+///
+/// [`Remapping`](crate::remap::Remapping) でプロジェクトを付け替えた後に呼ぶ。
+pub fn resolve_holidays(tasks: &mut [Task], calendar: &impl HolidayProvider) {
+    for task in tasks.iter_mut() {
+        task.holiday_name = task
+            .begin_time
+            .and_then(|d| calendar.holiday(&d.date(), task.project.as_ref().map(|p| p.id.as_str())))
+            .map(|n| n.to_string());
+        task.holiday = task.holiday_name.is_some();
+    }
 }
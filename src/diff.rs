@@ -0,0 +1,245 @@
+use crate::{analyzer::TasksAnalysisResult, AnalysisResult};
+use itertools::Itertools;
+use serde::Serialize;
+use std::ops::Sub;
+
+#[derive(Debug, Serialize)]
+pub struct DiffResult {
+    pub project_name: String,
+    pub all: TasksDiff,
+    /// グループ別
+    pub group: Vec<(String, TasksDiff)>,
+    /// 追加されたタスク
+    pub added: Vec<DiffTask>,
+    /// 削除されたタスク
+    pub removed: Vec<DiffTask>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy)]
+pub struct Delta<T> {
+    pub before: Option<T>,
+    pub after: Option<T>,
+    pub delta: Option<T>,
+}
+
+impl<T: Copy + Sub<Output = T>> Delta<T> {
+    fn new(before: Option<T>, after: Option<T>) -> Self {
+        Self {
+            before,
+            after,
+            delta: before.and_then(|b| after.map(|a| a - b)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TasksDiff {
+    pub total_estimated_time: Delta<i64>,
+    pub total_work_time: Delta<i64>,
+    pub total_time_gap_ratio: Delta<f64>,
+    pub work_days: Delta<i64>,
+    pub work_time_per_day: Delta<f64>,
+    pub work_time_per_day_max: Delta<i64>,
+    pub work_time_per_day_min: Delta<i64>,
+    pub work_time_per_day_median: Delta<i64>,
+    pub work_time_per_day_deviation: Delta<f64>,
+    pub work_time_per_value: Delta<f64>,
+    pub sessions: Delta<i64>,
+    pub session_time_avg: Delta<f64>,
+    pub session_time_max: Delta<i64>,
+    pub idle_time: Delta<i64>,
+    pub group_switches: Delta<i64>,
+    pub project_switches: Delta<i64>,
+}
+
+impl TasksDiff {
+    fn new(before: Option<&TasksAnalysisResult>, after: Option<&TasksAnalysisResult>) -> Self {
+        fn delta<T, F>(
+            before: Option<&TasksAnalysisResult>,
+            after: Option<&TasksAnalysisResult>,
+            f: F,
+        ) -> Delta<T>
+        where
+            T: Copy + Sub<Output = T>,
+            F: Fn(&TasksAnalysisResult) -> Option<T>,
+        {
+            Delta::new(before.and_then(&f), after.and_then(&f))
+        }
+
+        Self {
+            total_estimated_time: delta(before, after, |r| Some(r.total_estimated_time)),
+            total_work_time: delta(before, after, |r| Some(r.total_work_time)),
+            total_time_gap_ratio: delta(before, after, |r| r.total_time_gap_ratio),
+            work_days: delta(before, after, |r| Some(r.work_days)),
+            work_time_per_day: delta(before, after, |r| Some(r.work_time_per_day)),
+            work_time_per_day_max: delta(before, after, |r| Some(r.work_time_per_day_max)),
+            work_time_per_day_min: delta(before, after, |r| Some(r.work_time_per_day_min)),
+            work_time_per_day_median: delta(before, after, |r| Some(r.work_time_per_day_median)),
+            work_time_per_day_deviation: delta(before, after, |r| {
+                Some(r.work_time_per_day_deviation)
+            }),
+            work_time_per_value: delta(before, after, |r| r.work_time_per_value),
+            sessions: delta(before, after, |r| Some(r.sessions)),
+            session_time_avg: delta(before, after, |r| Some(r.session_time_avg)),
+            session_time_max: delta(before, after, |r| Some(r.session_time_max)),
+            idle_time: delta(before, after, |r| Some(r.idle_time)),
+            group_switches: delta(before, after, |r| Some(r.group_switches)),
+            project_switches: delta(before, after, |r| Some(r.project_switches)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq, Hash)]
+pub struct DiffTask {
+    pub id: String,
+    pub name: String,
+    /// 完了済みかどうか
+    pub done: bool,
+}
+
+impl DiffTask {
+    fn list(r: &AnalysisResult) -> Vec<Self> {
+        r.all
+            .tasks
+            .iter()
+            .map(|t| Self {
+                id: t.id.clone(),
+                name: t.name.clone(),
+                done: true,
+            })
+            .chain(r.remaining.tasks.iter().map(|t| Self {
+                id: t.id.clone(),
+                name: t.name.clone(),
+                done: false,
+            }))
+            .collect()
+    }
+}
+
+/// 2つの分析結果の各指標の差分と、タスクIDで突き合わせたタスクの増減を求める
+pub fn diff(before: &AnalysisResult, after: &AnalysisResult) -> DiffResult {
+    let group = group_keys(before)
+        .chain(group_keys(after))
+        .unique()
+        .sorted()
+        .map(|k| {
+            (
+                k.clone(),
+                TasksDiff::new(find_group(before, k), find_group(after, k)),
+            )
+        })
+        .collect();
+
+    let before_tasks = DiffTask::list(before);
+    let after_tasks = DiffTask::list(after);
+
+    DiffResult {
+        project_name: after.project_name.clone(),
+        all: TasksDiff::new(Some(&before.all), Some(&after.all)),
+        group,
+        added: after_tasks
+            .iter()
+            .filter(|t| before_tasks.iter().all(|b| b.id != t.id))
+            .cloned()
+            .collect(),
+        removed: before_tasks
+            .iter()
+            .filter(|t| after_tasks.iter().all(|a| a.id != t.id))
+            .cloned()
+            .collect(),
+    }
+}
+
+fn group_keys(r: &AnalysisResult) -> impl Iterator<Item = &String> {
+    r.by_group.nodes.iter().map(|n| &n.key)
+}
+
+fn find_group<'a>(r: &'a AnalysisResult, key: &str) -> Option<&'a TasksAnalysisResult> {
+    r.by_group.find(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        grouping::GroupKey,
+        holiday::HolidayCalendar,
+        test_util::{analyze, at, task},
+        Query,
+    };
+    use chrono::Duration;
+
+    fn result(tasks: &[(&str, &str, u32)]) -> AnalysisResult {
+        let tasks = tasks
+            .iter()
+            .enumerate()
+            .map(|(i, (id, name, minutes))| {
+                let begin = at(i as u32 + 1, 9, 0);
+                task(id, name)
+                    .project("a", "A")
+                    .time(begin, begin + Duration::minutes(*minutes as i64))
+                    .build()
+            })
+            .collect();
+        analyze(tasks, "a", None).unwrap()
+    }
+
+    #[test]
+    fn tasks_are_matched_by_id() {
+        let before = result(&[("1", "執筆 1章", 60), ("2", "校正 1章", 30)]);
+        let after = result(&[("1", "執筆 1章（改稿）", 90), ("3", "図版 作成", 45)]);
+        let res = diff(&before, &after);
+
+        let ids = |tasks: &[DiffTask]| tasks.iter().map(|t| t.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&res.added), vec!["3"]);
+        assert_eq!(ids(&res.removed), vec!["2"]);
+        assert_eq!(res.all.total_work_time.before, Some(90));
+        assert_eq!(res.all.total_work_time.after, Some(135));
+        assert_eq!(res.all.total_work_time.delta, Some(45));
+        assert_eq!(res.all.work_days.delta, Some(0));
+    }
+
+    #[test]
+    fn groups_are_compared_by_name() {
+        let before = result(&[("1", "執筆 1章", 60), ("2", "校正 1章", 30)]);
+        let after = result(&[("1", "執筆 1章", 90), ("3", "図版 作成", 45)]);
+        let res = diff(&before, &after);
+
+        let groups: Vec<_> = res
+            .group
+            .iter()
+            .map(|(k, d)| {
+                let w = d.total_work_time;
+                (k.as_str(), w.before, w.after, w.delta)
+            })
+            .collect();
+        assert_eq!(
+            groups,
+            vec![
+                ("図版", None, Some(45), None),
+                ("執筆", Some(60), Some(90), Some(30)),
+                ("校正", Some(30), None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_are_compared_with_custom_groupings() {
+        let calendar = HolidayCalendar::default();
+        let query = Query::new()
+            .grouping(GroupKey::Month.into())
+            .calendar(&calendar);
+        let tasks = |minutes: i64| {
+            vec![task("1", "執筆 1章")
+                .time(at(1, 9, 0), at(1, 9, 0) + Duration::minutes(minutes))
+                .build()]
+        };
+        let before = query.analyze(tasks(30)).unwrap();
+        let after = query.analyze(tasks(45)).unwrap();
+        let res = diff(&before, &after);
+
+        assert_eq!(res.group.len(), 1);
+        assert_eq!(res.group[0].0, "執筆");
+        assert_eq!(res.group[0].1.total_work_time.delta, Some(15));
+    }
+}
@@ -0,0 +1,153 @@
+use crate::{
+    analyzer::{group_name, TasksAnalysisResult},
+    grouping::GroupTree,
+    Project, Task,
+};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct RemainingAnalysisResult {
+    /// 残タスクの合計見積時間
+    pub total_estimated_time: i64,
+    /// 残タスクの合計予測所要時間（見積時間を実績/予定の倍率で補正）
+    pub total_forecast_time: i64,
+    /// 完了済みタスクの合計所要時間と残タスクの合計予測所要時間の和
+    pub projected_total_work_time: i64,
+    /// 残りの稼働日数の予測
+    pub remaining_work_days: Option<f64>,
+    /// 完了予定日
+    pub estimated_completion_date: Option<NaiveDate>,
+    /// 残タスクの一覧
+    pub tasks: Vec<RemainingTask>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RemainingTask {
+    pub id: String,
+    pub name: String,
+    pub group: Option<String>,
+    pub project: Option<Project>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_time: Option<i64>,
+    /// 見積時間に掛けた実績/予定の倍率
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_gap_ratio: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forecast_time: Option<i64>,
+    /// 開始済みで終了していない場合の開始時刻
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub begin_time: Option<NaiveDateTime>,
+}
+
+impl From<Task> for RemainingTask {
+    fn from(task: Task) -> Self {
+        Self {
+            id: task.id,
+            group: group_name(&task.name),
+            name: task.name,
+            project: task.project,
+            comment: task.comment,
+            estimated_time: task
+                .estimated_time
+                .map(|t| t.num_minutes())
+                .filter(|t| *t != 0),
+            time_gap_ratio: None,
+            forecast_time: None,
+            begin_time: task.begin_time,
+        }
+    }
+}
+
+/// 完了済みタスクの実績から残タスクの所要時間と完了予定日を予測する
+///
+/// 倍率は残タスクと同じ工程の実績/予定を優先し、工程に実績がなければ全体の倍率を使う。
+pub(crate) fn forecast(
+    tasks: Vec<Task>,
+    all: &TasksAnalysisResult,
+    group: &GroupTree,
+) -> RemainingAnalysisResult {
+    let tasks: Vec<RemainingTask> = tasks
+        .into_iter()
+        .map(RemainingTask::from)
+        .map(|mut t| {
+            let key = t.group.clone().unwrap_or_else(|| "-".into());
+            let ratio = group
+                .find(&key)
+                .and_then(|g| g.total_time_gap_ratio)
+                .or(all.total_time_gap_ratio)
+                .unwrap_or(1.0);
+            t.time_gap_ratio = t.estimated_time.map(|_| ratio);
+            t.forecast_time = t.estimated_time.map(|e| (e as f64 * ratio).round() as i64);
+            t
+        })
+        .collect();
+
+    let total_estimated_time = tasks.iter().filter_map(|t| t.estimated_time).sum();
+    let total_forecast_time: i64 = tasks.iter().filter_map(|t| t.forecast_time).sum();
+
+    let remaining_work_days = if tasks.is_empty() || !all.work_time_per_day.is_normal() {
+        None
+    } else {
+        Some(total_forecast_time as f64 / all.work_time_per_day)
+    };
+
+    // 稼働日以外の日も含めたこれまでのペースで暦日に換算する
+    let estimated_completion_date = remaining_work_days.and_then(|d| {
+        let first = all.tasks.first()?.begin_time.date();
+        let last = all.tasks.last()?.begin_time.date();
+        let span = (last - first).num_days() + 1;
+        let days = (d * span as f64 / all.work_days as f64).ceil() as i64;
+        Some(last + Duration::days(days))
+    });
+
+    RemainingAnalysisResult {
+        total_estimated_time,
+        total_forecast_time,
+        projected_total_work_time: all.total_work_time + total_forecast_time,
+        remaining_work_days,
+        estimated_completion_date,
+        tasks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_util::{analyze, at, task},
+        Task,
+    };
+    use chrono::Duration;
+
+    fn book(id: &str, name: &str, estimated: i64, done: Option<i64>) -> Task {
+        let t = task(id, name).project("100", "本").estimated(estimated);
+        match done {
+            Some(m) => t.time(at(1, 9, 0), at(1, 9, 0) + Duration::minutes(m)),
+            None => t,
+        }
+        .build()
+    }
+
+    #[test]
+    fn group_without_record_falls_back_to_overall_ratio() {
+        let tasks = vec![
+            book("1", "執筆 第1章", 60, Some(120)),
+            book("2", "校正 第1章", 60, Some(60)),
+            book("3", "執筆 第2章", 30, None),
+            book("4", "図版 第2章", 40, None),
+        ];
+        let res = analyze(tasks, "100", None).unwrap().remaining;
+        let forecasts: Vec<_> = res
+            .tasks
+            .iter()
+            .map(|t| (t.id.as_str(), t.time_gap_ratio, t.forecast_time))
+            .collect();
+        assert_eq!(
+            forecasts,
+            vec![("3", Some(2.0), Some(60)), ("4", Some(1.5), Some(60))]
+        );
+        assert_eq!(res.total_forecast_time, 120);
+    }
+}
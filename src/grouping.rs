@@ -0,0 +1,391 @@
+use crate::{
+    analyzer::{TaskOrder, Tasks, TasksAnalysisResult},
+    routine::normalize_name,
+    AnalysisResultTask,
+};
+use chrono::{Datelike, Timelike, Weekday};
+use serde::{Serialize, Serializer};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+/// どの分類にも当てはまらないタスクのキー
+const NONE: &str = "-";
+
+/// タスクを分類する観点
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GroupKey {
+    /// プロジェクト名
+    Project,
+    /// 工程（タスク名の最初の単語）
+    Group,
+    /// 末尾の番号や日付を除いたタスク名
+    Name,
+    /// 定型・個別
+    Routine,
+    /// タスク名・コメントの `#タグ`（複数あればそれぞれに含める）
+    Tag,
+    /// 平日・休日
+    Day,
+    /// 曜日
+    Weekday,
+    /// ISO週（`2020-W18`）
+    Week,
+    /// 月（`2020-05`）
+    Month,
+    /// 開始時刻の時
+    Hour,
+    /// 休日の名称
+    HolidayName,
+    /// コメントに含まれるキーワード（複数あればそれぞれに含める）
+    Keyword(Vec<String>),
+}
+
+impl GroupKey {
+    /// レポートの見出しに使う名前
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Project => "プロジェクト",
+            Self::Group => "工程",
+            Self::Name => "タスク名",
+            Self::Routine => "種類",
+            Self::Tag => "タグ",
+            Self::Day => "平日休日",
+            Self::Weekday => "曜日",
+            Self::Week => "週",
+            Self::Month => "月",
+            Self::Hour => "時間帯",
+            Self::HolidayName => "休日名",
+            Self::Keyword(_) => "キーワード",
+        }
+    }
+
+    /// タスクが属する分類を（並び順、キー）で返す
+    pub(crate) fn keys(&self, t: &AnalysisResultTask) -> Vec<(i64, String)> {
+        let one = |k: Option<String>| vec![(0, k.unwrap_or_else(|| NONE.into()))];
+        let many = |ks: Vec<String>| {
+            if ks.is_empty() {
+                one(None)
+            } else {
+                ks.into_iter().map(|k| (0, k)).collect()
+            }
+        };
+
+        match self {
+            Self::Project => one(t.project.as_ref().map(|p| p.name.clone())),
+            Self::Group => one(t.group.clone()),
+            Self::Name => one(Some(normalize_name(&t.name))),
+            Self::Routine => one(Some(if t.routine { "定型" } else { "個別" }.into())),
+            Self::Tag => many(
+                t.name
+                    .split_whitespace()
+                    .chain(t.comment.iter().flat_map(|c| c.split_whitespace()))
+                    .filter_map(|w| w.strip_prefix('#'))
+                    .filter(|w| !w.is_empty())
+                    .map(|w| w.to_string())
+                    .fold(vec![], |mut v, w| {
+                        if !v.contains(&w) {
+                            v.push(w);
+                        }
+                        v
+                    }),
+            ),
+            Self::Day => one(Some(
+                if t.holiday || matches!(t.begin_time.weekday(), Weekday::Sat | Weekday::Sun) {
+                    "休日".into()
+                } else {
+                    "平日".into()
+                },
+            )),
+            Self::Weekday => {
+                let w = t.begin_time.weekday();
+                vec![(
+                    w.num_days_from_monday() as i64,
+                    WEEKDAYS[w.num_days_from_monday() as usize].into(),
+                )]
+            }
+            Self::Week => one(Some(t.begin_time.format("%G-W%V").to_string())),
+            Self::Month => one(Some(t.begin_time.format("%Y-%m").to_string())),
+            Self::Hour => vec![(
+                t.begin_time.hour() as i64,
+                format!("{:02}時", t.begin_time.hour()),
+            )],
+            Self::HolidayName => one(t.holiday_name.clone()),
+            Self::Keyword(words) => many(
+                words
+                    .iter()
+                    .filter(|w| t.comment.as_ref().map(|c| c.contains(*w)).unwrap_or(false))
+                    .cloned()
+                    .collect(),
+            ),
+        }
+    }
+}
+
+const WEEKDAYS: [&str; 7] = ["月", "火", "水", "木", "金", "土", "日"];
+
+impl Display for GroupKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Project => write!(f, "project"),
+            Self::Group => write!(f, "group"),
+            Self::Name => write!(f, "name"),
+            Self::Routine => write!(f, "routine"),
+            Self::Tag => write!(f, "tag"),
+            Self::Day => write!(f, "day"),
+            Self::Weekday => write!(f, "weekday"),
+            Self::Week => write!(f, "week"),
+            Self::Month => write!(f, "month"),
+            Self::Hour => write!(f, "hour"),
+            Self::HolidayName => write!(f, "holiday-name"),
+            Self::Keyword(words) => write!(f, "keyword={}", words.join(",")),
+        }
+    }
+}
+
+impl FromStr for GroupKey {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(words) = s.strip_prefix("keyword=") {
+            let words: Vec<_> = words
+                .split(',')
+                .map(|w| w.trim())
+                .filter(|w| !w.is_empty())
+                .map(|w| w.to_string())
+                .collect();
+            if words.is_empty() {
+                return Err("no keywords");
+            }
+            return Ok(Self::Keyword(words));
+        }
+
+        match s {
+            "project" => Ok(Self::Project),
+            "group" => Ok(Self::Group),
+            "name" => Ok(Self::Name),
+            "routine" => Ok(Self::Routine),
+            "tag" => Ok(Self::Tag),
+            "day" => Ok(Self::Day),
+            "weekday" => Ok(Self::Weekday),
+            "week" => Ok(Self::Week),
+            "month" => Ok(Self::Month),
+            "hour" => Ok(Self::Hour),
+            "holiday-name" => Ok(Self::HolidayName),
+            _ => Err("invalid group key"),
+        }
+    }
+}
+
+impl Serialize for GroupKey {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(self)
+    }
+}
+
+/// `month/group` のように `/` で区切って入れ子にした分類の観点
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Grouping(pub Vec<GroupKey>);
+
+impl Grouping {
+    /// 平日休日別・工程別・種類（定型・個別）別（analyze の既定）
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            GroupKey::Day.into(),
+            GroupKey::Group.into(),
+            GroupKey::Routine.into(),
+        ]
+    }
+
+    /// レポートの見出しに使う名前（`月・工程`）
+    pub fn label(&self) -> String {
+        self.0
+            .iter()
+            .map(|k| k.label())
+            .collect::<Vec<_>>()
+            .join("・")
+    }
+}
+
+impl Display for Grouping {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.0
+                .iter()
+                .map(|k| k.to_string())
+                .collect::<Vec<_>>()
+                .join("/")
+        )
+    }
+}
+
+impl From<GroupKey> for Grouping {
+    fn from(key: GroupKey) -> Self {
+        Self(vec![key])
+    }
+}
+
+impl FromStr for Grouping {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(
+            s.split('/')
+                .map(|k| k.trim().parse())
+                .collect::<Result<_, _>>()?,
+        ))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct GroupTree {
+    pub grouping: Grouping,
+    pub nodes: Vec<GroupNode>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GroupNode {
+    pub key: String,
+    pub result: TasksAnalysisResult,
+    /// 次の観点で分類したもの（最後の観点ではなし）
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<GroupNode>,
+}
+
+impl GroupTree {
+    pub(crate) fn order_tasks(&mut self, order: &TaskOrder) {
+        fn walk(nodes: &mut [GroupNode], order: &TaskOrder) {
+            for n in nodes {
+                n.result.order_tasks(order);
+                walk(&mut n.children, order);
+            }
+        }
+        walk(&mut self.nodes, order);
+    }
+
+    /// キーでたどった分類の集計を返す
+    pub fn find(&self, key: &str) -> Option<&TasksAnalysisResult> {
+        self.nodes.iter().find(|n| n.key == key).map(|n| &n.result)
+    }
+}
+
+pub(crate) fn group_tree(tasks: &Tasks, grouping: &Grouping) -> GroupTree {
+    GroupTree {
+        grouping: grouping.clone(),
+        nodes: nodes(tasks, &grouping.0),
+    }
+}
+
+fn nodes(tasks: &Tasks, keys: &[GroupKey]) -> Vec<GroupNode> {
+    let (key, rest) = match keys.split_first() {
+        Some(k) => k,
+        None => return vec![],
+    };
+
+    let mut groups: BTreeMap<(i64, String), Vec<AnalysisResultTask>> = BTreeMap::new();
+    for t in tasks.0.iter() {
+        for k in key.keys(t) {
+            groups.entry(k).or_default().push(t.clone());
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|((_, key), v)| {
+            let sub = Tasks(v, tasks.1, tasks.2, tasks.3.clone());
+            GroupNode {
+                key,
+                children: nodes(&sub, rest),
+                result: sub.analyze(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        holiday::HolidayCalendar,
+        test_util::{at, task},
+        Query, Task,
+    };
+
+    fn tree(tasks: Vec<Task>, grouping: &str) -> GroupTree {
+        let calendar = HolidayCalendar::default();
+        let mut res = Query::new()
+            .grouping(grouping.parse().unwrap())
+            .calendar(&calendar)
+            .analyze(tasks)
+            .unwrap();
+        res.groups.remove(0)
+    }
+
+    /// 分類のキーを `/` でつないだパスと作業時間の一覧
+    fn summary(nodes: &[GroupNode]) -> Vec<(String, i64)> {
+        nodes
+            .iter()
+            .flat_map(|n| {
+                std::iter::once((n.key.clone(), n.result.total_work_time)).chain(
+                    summary(&n.children)
+                        .into_iter()
+                        .map(move |(k, v)| (format!("{}/{}", n.key, k), v)),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn nested_groups_split_each_node() {
+        let tasks = vec![
+            task("1", "執筆 1章")
+                .time(at(1, 9, 0), at(1, 10, 0))
+                .build(),
+            task("2", "校正 1章")
+                .time(at(2, 9, 0), at(2, 9, 30))
+                .build(),
+            task("3", "執筆 2章")
+                .time(at(6, 9, 0), at(6, 9, 45))
+                .build(),
+        ];
+        let tree = tree(tasks, "day/group");
+        assert_eq!(
+            summary(&tree.nodes),
+            vec![
+                ("休日".to_string(), 45),
+                ("休日/執筆".to_string(), 45),
+                ("平日".to_string(), 90),
+                ("平日/執筆".to_string(), 60),
+                ("平日/校正".to_string(), 30),
+            ]
+        );
+    }
+
+    #[test]
+    fn tasks_with_several_tags_are_in_each_group() {
+        let tasks = vec![
+            task("1", "執筆 #本 #急ぎ")
+                .time(at(1, 9, 0), at(1, 10, 0))
+                .build(),
+            task("2", "校正 #本")
+                .time(at(1, 10, 0), at(1, 10, 30))
+                .build(),
+            task("3", "メール")
+                .time(at(1, 11, 0), at(1, 11, 15))
+                .build(),
+        ];
+        let tree = tree(tasks, "tag");
+        assert_eq!(
+            summary(&tree.nodes),
+            vec![
+                ("-".to_string(), 15),
+                ("急ぎ".to_string(), 60),
+                ("本".to_string(), 90)
+            ]
+        );
+    }
+}
@@ -1,26 +1,500 @@
-use chrono::NaiveDate;
+use chrono::{Datelike, Duration, NaiveDate};
+use encoding_rs::SHIFT_JIS;
+use encoding_rs_io::DecodeReaderBytes;
 use lazy_static::lazy_static;
-use serde::Deserialize;
-use std::collections::HashMap;
+use serde::{Deserialize, Deserializer};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::{self, File},
+    io::{BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+};
 
 // https://www8.cao.go.jp/chosei/shukujitsu/syukujitsu.csv
 const DATA: &[u8] = include_bytes!("syukujitsu.csv");
 
 #[derive(Debug, Deserialize)]
 struct Holiday {
-    #[serde(rename = "国民の祝日・休日月日")]
+    #[serde(rename = "国民の祝日・休日月日", deserialize_with = "deserialize_date")]
     date: NaiveDate,
     #[serde(rename = "国民の祝日・休日名称")]
     name: String,
 }
 
+fn deserialize_date<'de, D: Deserializer<'de>>(d: D) -> Result<NaiveDate, D::Error> {
+    let s = String::deserialize(d)?;
+    NaiveDate::parse_from_str(&s, "%Y/%m/%d").map_err(serde::de::Error::custom)
+}
+
 lazy_static! {
-    pub static ref HOLIDAYS: HashMap<NaiveDate, String> = csv::ReaderBuilder::new()
+    /// `tcc holiday update` で保存した祝日データが組み込みのデータより新しければそれを、なければ組み込みのデータを使う
+    pub static ref HOLIDAYS: HashMap<NaiveDate, String> = newer_holidays(
+        parse_holidays(DATA),
+        user_data_path()
+            .and_then(|p| fs::read(p).ok())
+            .map(|d| parse_holidays(&d)),
+    );
+}
+
+/// 最後の祝日がより先にある方の祝日データを返す（同じなら更新したデータ）
+fn newer_holidays(
+    builtin: HashMap<NaiveDate, String>,
+    user: Option<HashMap<NaiveDate, String>>,
+) -> HashMap<NaiveDate, String> {
+    match user {
+        Some(user) if user.keys().max() >= builtin.keys().max() && !user.is_empty() => user,
+        _ => builtin,
+    }
+}
+
+fn parse_holidays(data: &[u8]) -> HashMap<NaiveDate, String> {
+    csv::ReaderBuilder::new()
         .delimiter(b',')
         .has_headers(true)
-        .from_reader(DATA)
+        .from_reader(data)
         .deserialize::<Holiday>()
-        .into_iter()
         .filter_map(|h| h.ok().map(|h2| (h2.date, h2.name)))
-        .collect();
+        .collect()
+}
+
+/// 更新した祝日データの保存先
+pub fn user_data_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|d| d.join("tcc").join("syukujitsu.csv"))
+}
+
+/// 内閣府の `syukujitsu.csv`（Shift_JIS）をUTF-8にしてユーザーデータとして保存し、保存先と祝日の数を返す
+pub fn update(data: &[u8]) -> Result<(PathBuf, usize), Box<dyn Error>> {
+    let (text, count) = decode_holidays(data)?;
+    let path = user_data_path().ok_or("data directory is not found")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, text)?;
+    Ok((path, count))
+}
+
+/// Shift_JIS（またはUTF-8）の祝日データをUTF-8にして、祝日の数とともに返す
+fn decode_holidays(data: &[u8]) -> Result<(String, usize), Box<dyn Error>> {
+    let text = match std::str::from_utf8(data) {
+        Ok(t) => t.trim_start_matches('\u{feff}').to_string(),
+        Err(_) => {
+            let (t, _, malformed) = SHIFT_JIS.decode(data);
+            if malformed {
+                return Err("invalid encoding".into());
+            }
+            t.into_owned()
+        }
+    };
+    let count = parse_holidays(text.as_bytes()).len();
+    if count == 0 {
+        return Err("no holidays found".into());
+    }
+    Ok((text, count))
+}
+
+/// 日付が休日かどうかを判定する
+pub trait HolidayProvider {
+    /// 休日であればその名称を返す（プロジェクトごとの設定があればそちらを優先する）
+    fn holiday(&self, date: &NaiveDate, project_id: Option<&str>) -> Option<&str>;
+}
+
+/// 組み込みの祝日に、ユーザーが用意したCSV・ICSファイルの休日を重ねたカレンダー
+///
+/// 名称が `None` の日は、下にあるカレンダーで休日になっていても稼働日として扱う。
+#[derive(Debug, Clone, Default)]
+pub struct HolidayCalendar {
+    days: HashMap<NaiveDate, Option<String>>,
+    projects: HashMap<String, HashMap<NaiveDate, Option<String>>>,
+    /// `person` 列を指定した行（[`HolidayCalendar::for_person`] で選ぶまで使わない）
+    people: HashMap<String, HolidayCalendar>,
+    coverage: Option<(NaiveDate, NaiveDate)>,
+}
+
+impl HolidayProvider for HolidayCalendar {
+    fn holiday(&self, date: &NaiveDate, project_id: Option<&str>) -> Option<&str> {
+        project_id
+            .and_then(|p| self.projects.get(p))
+            .and_then(|days| days.get(date))
+            .or_else(|| self.days.get(date))
+            .and_then(|n| n.as_deref())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct UserHoliday {
+    #[serde(alias = "国民の祝日・休日月日")]
+    date: String,
+    #[serde(default, alias = "国民の祝日・休日名称")]
+    name: Option<String>,
+    #[serde(default)]
+    project: Option<String>,
+    #[serde(default)]
+    person: Option<String>,
+}
+
+/// ICSの予定に名前（SUMMARY）がないときの休日の名称
+const DEFAULT_EVENT_NAME: &str = "休日";
+
+impl HolidayCalendar {
+    /// 組み込みの日本の祝日
+    pub fn builtin() -> Self {
+        Self {
+            days: HOLIDAYS
+                .iter()
+                .map(|(d, n)| (*d, Some(n.clone())))
+                .collect(),
+            projects: HashMap::new(),
+            people: HashMap::new(),
+            coverage: HOLIDAYS
+                .keys()
+                .min()
+                .zip(HOLIDAYS.keys().max())
+                .map(|(b, e)| {
+                    (
+                        NaiveDate::from_ymd(b.year(), 1, 1),
+                        NaiveDate::from_ymd(e.year(), 12, 31),
+                    )
+                }),
+        }
+    }
+
+    /// 組み込みの祝日データが対象とする期間（その年の1月1日から最後の年の12月31日まで）
+    pub fn coverage(&self) -> Option<(NaiveDate, NaiveDate)> {
+        self.coverage
+    }
+
+    /// 休日の一覧を日付順に返す（プロジェクトを指定するとその設定を反映する）
+    pub fn list(&self, project_id: Option<&str>) -> Vec<(NaiveDate, &str)> {
+        let mut dates: Vec<_> = self.days.keys().collect();
+        if let Some(days) = project_id.and_then(|p| self.projects.get(p)) {
+            dates.extend(days.keys());
+        }
+        dates.sort();
+        dates.dedup();
+        dates
+            .into_iter()
+            .filter_map(|d| self.holiday(d, project_id).map(|n| (*d, n)))
+            .collect()
+    }
+
+    /// 後から重ねたカレンダーの設定を優先してまとめる
+    pub fn merge(&mut self, other: Self) {
+        self.coverage = self.coverage.or(other.coverage);
+        self.days.extend(other.days);
+        for (p, days) in other.projects {
+            self.projects.entry(p).or_default().extend(days);
+        }
+        for (p, calendar) in other.people {
+            self.people.entry(p).or_default().merge(calendar);
+        }
+    }
+
+    /// 指定した人の行を他の行より優先して反映し、他の人の行を捨てる
+    pub fn for_person(mut self, person: Option<&str>) -> Self {
+        let mut people = std::mem::take(&mut self.people);
+        if let Some(calendar) = person.and_then(|p| people.remove(p)) {
+            self.merge(calendar);
+        }
+        self
+    }
+
+    /// すべての休日を指定したプロジェクトのタスクだけに適用する
+    pub fn for_project(mut self, project_id: &str) -> Self {
+        let mut days = std::mem::take(&mut self.days);
+        days.extend(self.projects.remove(project_id).unwrap_or_default());
+        self.projects.clear();
+        self.projects.insert(project_id.to_string(), days);
+        for calendar in self.people.values_mut() {
+            *calendar = std::mem::take(calendar).for_project(project_id);
+        }
+        self
+    }
+
+    /// 拡張子が `.ics` ならICS、それ以外はCSVとして読み込む
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let ics = path
+            .as_ref()
+            .extension()
+            .map(|e| e.eq_ignore_ascii_case("ics"))
+            .unwrap_or(false);
+        let file = File::open(path)?;
+        if ics {
+            Self::from_ics(file)
+        } else {
+            Self::from_csv(file)
+        }
+    }
+
+    /// `date,name,project,person` 列のCSVを読み込む
+    ///
+    /// `date` は `2020-08-13` や `2020/8/13` の日付か `2020-08-13..2020-08-16` の範囲、
+    /// `name` が空の行は稼働日、`project` を指定した行はそのプロジェクトだけに、
+    /// `person` を指定した行は [`HolidayCalendar::for_person`] でその人を選んだときだけ適用する。
+    pub fn from_csv(r: impl Read) -> Result<Self, Box<dyn Error>> {
+        let mut calendar = Self::default();
+        for h in csv::ReaderBuilder::new()
+            .has_headers(true)
+            .flexible(true)
+            .from_reader(DecodeReaderBytes::new(r))
+            .deserialize::<UserHoliday>()
+        {
+            let h = h?;
+            let (begin, end) = parse_range(&h.date)?;
+            let name = h.name.filter(|n| !n.is_empty());
+            let project = h.project.filter(|p| !p.is_empty());
+            let target = match h.person.filter(|p| !p.is_empty()) {
+                Some(p) => calendar.people.entry(p).or_default(),
+                None => &mut calendar,
+            };
+            for date in days(begin, end) {
+                target.insert(date, name.clone(), project.clone());
+            }
+        }
+        Ok(calendar)
+    }
+
+    /// ICSファイルの終日の予定を休日として読み込む（時刻つきの予定は読み飛ばす）
+    pub fn from_ics(r: impl Read) -> Result<Self, Box<dyn Error>> {
+        let mut calendar = Self::default();
+        // 開始日（終日の予定のときだけ）、終了日、名前
+        let mut event: Option<(Option<NaiveDate>, Option<NaiveDate>, Option<String>)> = None;
+
+        for line in unfold(BufReader::new(DecodeReaderBytes::new(r)))? {
+            let (key, value) = match line.find(':') {
+                Some(i) => (&line[..i], &line[i + 1..]),
+                None => continue,
+            };
+            let (name, params) = match key.find(';') {
+                Some(i) => (&key[..i], &key[i + 1..]),
+                None => (key, ""),
+            };
+
+            match (name, value) {
+                ("BEGIN", "VEVENT") => event = Some((None, None, None)),
+                ("END", "VEVENT") => {
+                    if let Some((Some(begin), end, summary)) = event.take() {
+                        // DTEND は終日の予定では翌日を指す
+                        let end = end.map(|e| e - Duration::days(1)).unwrap_or(begin);
+                        let name = summary
+                            .filter(|s| !s.trim().is_empty())
+                            .unwrap_or_else(|| DEFAULT_EVENT_NAME.into());
+                        for date in days(begin, end.max(begin)) {
+                            calendar.insert(date, Some(name.clone()), None);
+                        }
+                    }
+                }
+                ("DTSTART", _) => {
+                    if let Some(e) = event.as_mut() {
+                        if is_ics_date(params, value) {
+                            e.0 = Some(parse_ics_date(value)?);
+                        }
+                    }
+                }
+                ("DTEND", _) => {
+                    if let Some(e) = event.as_mut() {
+                        e.1 = Some(parse_ics_date(value)?);
+                    }
+                }
+                ("SUMMARY", _) => {
+                    if let Some(e) = event.as_mut() {
+                        e.2 = Some(value.replace("\\,", ",").replace("\\;", ";"));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(calendar)
+    }
+
+    fn insert(&mut self, date: NaiveDate, name: Option<String>, project: Option<String>) {
+        match project {
+            Some(p) => {
+                self.projects.entry(p).or_default().insert(date, name);
+            }
+            None => {
+                self.days.insert(date, name);
+            }
+        }
+    }
+}
+
+fn parse_date(s: &str) -> Result<NaiveDate, Box<dyn Error>> {
+    let s = s.trim();
+    Ok(NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(s, "%Y/%m/%d"))?)
+}
+
+fn parse_range(s: &str) -> Result<(NaiveDate, NaiveDate), Box<dyn Error>> {
+    match s.find("..") {
+        Some(i) => Ok((parse_date(&s[..i])?, parse_date(&s[i + 2..])?)),
+        None => {
+            let d = parse_date(s)?;
+            Ok((d, d))
+        }
+    }
+}
+
+/// `VALUE=DATE` の値か、時刻のない値なら終日の日付とみなす
+fn is_ics_date(params: &str, value: &str) -> bool {
+    params
+        .split(';')
+        .any(|p| p.eq_ignore_ascii_case("VALUE=DATE"))
+        || !value.contains('T')
+}
+
+fn parse_ics_date(s: &str) -> Result<NaiveDate, Box<dyn Error>> {
+    Ok(NaiveDate::parse_from_str(
+        s.get(..8).ok_or("invalid date")?,
+        "%Y%m%d",
+    )?)
+}
+
+fn days(begin: NaiveDate, end: NaiveDate) -> impl Iterator<Item = NaiveDate> {
+    (0..=(end - begin).num_days()).map(move |d| begin + Duration::days(d))
+}
+
+/// 折り返された行（空白で始まる行）を前の行につなげる
+fn unfold(r: impl BufRead) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut lines: Vec<String> = vec![];
+    for line in r.lines() {
+        let line = line?;
+        let line = line.trim_end_matches('\r');
+        match (
+            line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')),
+            lines.last_mut(),
+        ) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CSV: &str =
+        "国民の祝日・休日月日,国民の祝日・休日名称\r\n2020/1/1,元日\r\n2020/5/6,休日\r\n";
+
+    #[test]
+    fn update_decodes_shift_jis_and_utf8() {
+        let (sjis, _, _) = SHIFT_JIS.encode(CSV);
+        let (text, count) = decode_holidays(&sjis).unwrap();
+        assert_eq!((text.as_str(), count), (CSV, 2));
+
+        let bom = format!("\u{feff}{}", CSV);
+        assert_eq!(
+            decode_holidays(bom.as_bytes()).unwrap(),
+            (CSV.to_string(), 2)
+        );
+
+        assert!(decode_holidays(b"date,name\n").is_err());
+        assert!(decode_holidays(&[0x82, 0xff, 0xff]).is_err());
+    }
+
+    #[test]
+    fn newer_user_data_is_preferred() {
+        let holidays = |dates: &[(i32, u32, u32)]| -> HashMap<NaiveDate, String> {
+            dates
+                .iter()
+                .map(|(y, m, d)| (NaiveDate::from_ymd(*y, *m, *d), "祝日".to_string()))
+                .collect()
+        };
+        let builtin = holidays(&[(2020, 1, 1), (2021, 1, 1)]);
+        let older = holidays(&[(2020, 1, 1)]);
+        let newer = holidays(&[(2022, 1, 1)]);
+
+        assert_eq!(newer_holidays(builtin.clone(), Some(older)), builtin);
+        assert_eq!(newer_holidays(builtin.clone(), Some(newer.clone())), newer);
+        assert_eq!(
+            newer_holidays(builtin.clone(), Some(HashMap::new())),
+            builtin
+        );
+        assert_eq!(newer_holidays(builtin.clone(), None), builtin);
+    }
+
+    #[test]
+    fn ics_all_day_events_are_holidays() {
+        let ics = "BEGIN:VCALENDAR\r\n\
+BEGIN:VEVENT\r\n\
+DTSTART;VALUE=DATE:20200813\r\n\
+DTEND;VALUE=DATE:20200815\r\n\
+SUMMARY:夏季\r\n \
+休暇\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+DTSTART:20200817T100000\r\n\
+DTEND:20200817T110000\r\n\
+SUMMARY:打ち合わせ\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+DTSTART;VALUE=DATE:20200818\r\n\
+END:VEVENT\r\n\
+END:VCALENDAR\r\n";
+        let calendar = HolidayCalendar::from_ics(ics.as_bytes()).unwrap();
+        let d = |day| NaiveDate::from_ymd(2020, 8, day);
+        assert_eq!(calendar.holiday(&d(13), None), Some("夏季休暇"));
+        assert_eq!(calendar.holiday(&d(14), None), Some("夏季休暇"));
+        assert_eq!(calendar.holiday(&d(15), None), None);
+        assert_eq!(calendar.holiday(&d(17), None), None);
+        assert_eq!(calendar.holiday(&d(18), None), Some(DEFAULT_EVENT_NAME));
+    }
+
+    fn csv(rows: &str) -> HolidayCalendar {
+        HolidayCalendar::from_csv(format!("date,name,project,person\n{}", rows).as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn later_calendars_and_project_rows_win() {
+        let d = |day| NaiveDate::from_ymd(2020, 8, day);
+        let mut calendar = csv("2020-08-13,お盆,,\n2020-08-14,お盆,,\n");
+        calendar.merge(csv(
+            "2020-08-14,,,\n2020-08-17,夏休み,,\n2020-08-13,,100,\n",
+        ));
+
+        assert_eq!(calendar.holiday(&d(13), None), Some("お盆"));
+        assert_eq!(calendar.holiday(&d(13), Some("100")), None);
+        assert_eq!(calendar.holiday(&d(13), Some("200")), Some("お盆"));
+        assert_eq!(calendar.holiday(&d(14), None), None);
+        assert_eq!(calendar.holiday(&d(17), None), Some("夏休み"));
+    }
+
+    #[test]
+    fn person_rows_apply_only_to_that_person() {
+        let d = |day| NaiveDate::from_ymd(2020, 8, day);
+        let rows =
+            "2020-08-13,お盆,,\n2020-08-13,,,alice\n2020-08-14,有給,,alice\n2020-08-17,有給,,bob\n";
+
+        let alice = csv(rows).for_person(Some("alice"));
+        assert_eq!(alice.holiday(&d(13), None), None);
+        assert_eq!(alice.holiday(&d(14), None), Some("有給"));
+        assert_eq!(alice.holiday(&d(17), None), None);
+
+        let nobody = csv(rows).for_person(None);
+        assert_eq!(nobody.holiday(&d(13), None), Some("お盆"));
+        assert_eq!(nobody.holiday(&d(14), None), None);
+        assert_eq!(nobody.holiday(&d(17), None), None);
+    }
+
+    #[test]
+    fn project_files_apply_only_to_that_project() {
+        let d = |day| NaiveDate::from_ymd(2020, 8, day);
+        let file =
+            csv("2020-08-13,お盆,,\n2020-08-14,,100,\n2020-08-17,,200,\n2020-08-18,有給,,alice\n");
+
+        let mut calendar = csv("2020-08-14,お盆,,\n2020-08-17,お盆,,\n");
+        calendar.merge(file.for_person(Some("alice")).for_project("100"));
+
+        assert_eq!(calendar.holiday(&d(13), None), None);
+        assert_eq!(calendar.holiday(&d(13), Some("100")), Some("お盆"));
+        assert_eq!(calendar.holiday(&d(14), Some("100")), None);
+        assert_eq!(calendar.holiday(&d(14), Some("200")), Some("お盆"));
+        // ほかのプロジェクトの行は捨てる
+        assert_eq!(calendar.holiday(&d(17), Some("200")), Some("お盆"));
+        assert_eq!(calendar.holiday(&d(18), Some("100")), Some("有給"));
+        assert_eq!(calendar.holiday(&d(18), None), None);
+    }
 }
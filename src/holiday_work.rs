@@ -0,0 +1,126 @@
+use crate::{holiday::HolidayProvider, AnalysisResultTask};
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use itertools::Itertools;
+use serde::Serialize;
+
+/// 連休をさかのぼる・先に進める最大の日数
+const MAX_RUN: i64 = 31;
+
+#[derive(Debug, Serialize)]
+pub struct HolidayWork {
+    /// 期間に含まれる休日の名称、または連休
+    pub name: String,
+    pub begin: NaiveDate,
+    pub end: NaiveDate,
+    /// 作業した日数
+    pub work_days: i64,
+    /// 作業時間
+    pub work_time: i64,
+    pub tasks: Vec<AnalysisResultTask>,
+}
+
+/// 休日・連休の期間ごとに、その期間に行ったタスクをまとめる
+///
+/// 土日とカレンダーの休日が続く期間のうち、休日を含むもの、または3日以上続くものを対象とする。
+/// お盆・年末年始のような会社の休みは、休日カレンダーのファイルで指定したものだけを休日とみなす。
+pub(crate) fn holiday_work(
+    tasks: &[AnalysisResultTask],
+    project_id: Option<&str>,
+    calendar: &dyn HolidayProvider,
+) -> Vec<HolidayWork> {
+    let off = |d: &NaiveDate| {
+        matches!(d.weekday(), Weekday::Sat | Weekday::Sun)
+            || calendar.holiday(d, project_id).is_some()
+    };
+    let period = |d: NaiveDate| {
+        let mut begin = d;
+        while off(&(begin - Duration::days(1))) && (d - begin).num_days() < MAX_RUN {
+            begin -= Duration::days(1);
+        }
+        let mut end = d;
+        while off(&(end + Duration::days(1))) && (end - d).num_days() < MAX_RUN {
+            end += Duration::days(1);
+        }
+        (begin, end)
+    };
+
+    tasks
+        .iter()
+        .filter(|t| off(&t.begin_time.date()))
+        .map(|t| (period(t.begin_time.date()), t))
+        .sorted_by_key(|((b, _), t)| (*b, t.begin_time))
+        .group_by(|(p, _)| *p)
+        .into_iter()
+        .filter_map(|((begin, end), tasks)| {
+            let tasks: Vec<_> = tasks.map(|(_, t)| t.clone()).collect();
+            let days: Vec<_> = (0..=(end - begin).num_days())
+                .map(|d| begin + Duration::days(d))
+                .collect();
+            let names: Vec<_> = days
+                .iter()
+                .filter_map(|d| calendar.holiday(d, project_id))
+                .unique()
+                .collect();
+            let name = Some(names.join("・"))
+                .filter(|_| !names.is_empty())
+                .or_else(|| {
+                    if days.len() >= 3 {
+                        Some("連休".to_string())
+                    } else {
+                        None
+                    }
+                })?;
+
+            Some(HolidayWork {
+                name,
+                begin,
+                end,
+                work_days: tasks.iter().map(|t| t.begin_time.date()).unique().count() as i64,
+                work_time: tasks.iter().map(|t| t.timespan).sum(),
+                tasks,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        holiday::HolidayCalendar,
+        test_util::{at, day, task},
+    };
+
+    #[test]
+    fn work_is_grouped_by_holiday_period() {
+        let calendar = HolidayCalendar::from_csv(
+            "date,name,project\n2020-06-19,創立記念日,\n2020-06-26,夏休み,a\n".as_bytes(),
+        )
+        .unwrap();
+        let tasks = [
+            // 祝日のない週末は含めない
+            task("1", "1").time(at(13, 10, 0), at(13, 11, 0)).done(),
+            task("2", "2").time(at(19, 10, 0), at(19, 10, 30)).done(),
+            task("3", "3").time(at(20, 10, 0), at(20, 10, 45)).done(),
+            task("4", "4").time(at(26, 10, 0), at(26, 11, 0)).done(),
+        ];
+        let summary = |res: Vec<HolidayWork>| {
+            res.into_iter()
+                .map(|h| (h.name, h.begin, h.end, h.work_days, h.work_time))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            summary(holiday_work(&tasks, Some("a"), &calendar)),
+            vec![
+                ("創立記念日".to_string(), day(19), day(21), 2, 75),
+                ("夏休み".to_string(), day(26), day(28), 1, 60),
+            ]
+        );
+        // プロジェクトの休日はほかのプロジェクトでは休日にならない
+        assert_eq!(
+            summary(holiday_work(&tasks, Some("b"), &calendar)),
+            vec![("創立記念日".to_string(), day(19), day(21), 2, 75)]
+        );
+    }
+}
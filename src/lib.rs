@@ -1,22 +1,51 @@
-pub use analyzer::{analyze, AnalysisResult, AnalysisResultTask};
-use chrono::{Duration, NaiveDateTime};
+pub use analyzer::{
+    analyze, AnalysisResult, AnalysisResultTask, SortKey, TaskOrder, TasksAnalysisResult,
+};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+pub use compare::{compare, CompareResult};
+pub use diff::{diff, DiffResult};
+pub use query::Query;
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
 mod analyzer;
+mod compare;
 pub mod csv_parser;
-mod holiday;
+mod diff;
+mod forecast;
+pub mod grouping;
+pub mod holiday;
+mod holiday_work;
+pub mod lint;
 pub mod markdown;
+pub mod pivot;
+pub mod projects;
+mod query;
+pub mod remap;
+pub mod routine;
+pub mod schema;
+#[cfg(test)]
+mod test_util;
+pub mod unassigned;
+pub mod working_hours;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Task {
     pub id: String,
     pub name: String,
+    /// CSVの行番号
+    pub row: u64,
+    /// 実行日
+    pub date: NaiveDate,
+    /// 見積時間（00:00 のときは0分）
     pub estimated_time: Option<Duration>,
     pub begin_time: Option<NaiveDateTime>,
     pub end_time: Option<NaiveDateTime>,
     pub comment: Option<String>,
     pub project: Option<Project>,
     pub holiday: bool,
+    /// 休日の名称
+    pub holiday_name: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone)]
@@ -24,3 +53,41 @@ pub struct Project {
     pub id: String,
     pub name: String,
 }
+
+/// `2020-05-01..2020-05-31` 形式の日付の範囲（両端を含む、どちらかを省略可能）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateRange {
+    pub begin: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+}
+
+impl DateRange {
+    pub fn contains(&self, date: &NaiveDate) -> bool {
+        self.begin.map(|b| b <= *date).unwrap_or(true)
+            && self.end.map(|e| *date <= e).unwrap_or(true)
+    }
+}
+
+impl FromStr for DateRange {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn parse(s: &str) -> Result<Option<NaiveDate>, &'static str> {
+            if s.is_empty() {
+                return Ok(None);
+            }
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .map(Some)
+                .map_err(|_| "invalid date")
+        }
+
+        let (begin, end) = match s.find("..") {
+            Some(i) => (parse(&s[..i])?, parse(&s[i + 2..])?),
+            None => {
+                let d = parse(s)?;
+                (d, d)
+            }
+        };
+        Ok(Self { begin, end })
+    }
+}
@@ -0,0 +1,224 @@
+use crate::{holiday::HolidayProvider, Task};
+use chrono::Duration;
+use itertools::Itertools;
+use serde::Serialize;
+use std::fmt::{self, Display};
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Error => "error",
+                Self::Warning => "warning",
+                Self::Info => "info",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingKind {
+    /// 他のタスクと時間が重なっている
+    Overlap,
+    /// 終了時刻が開始時刻より前で、日をまたいだものとすると所要時間が長すぎる
+    EndBeforeBegin,
+    /// 所要時間が長すぎる
+    TooLong,
+    /// 所要時間が0分
+    ZeroLength,
+    /// タスクIDが重複している
+    DuplicateId,
+    /// 見積時間が0分
+    ZeroEstimate,
+    /// 休日に実行されている
+    Holiday,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Finding {
+    pub severity: Severity,
+    pub kind: FindingKind,
+    /// CSVの行番号
+    pub row: u64,
+    pub task_id: String,
+    pub task_name: String,
+    pub message: String,
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}行目 {} ({}): {}",
+            self.severity, self.row, self.task_name, self.task_id, self.message
+        )
+    }
+}
+
+/// タスクの記録の不整合を検出する（`max_time` より長いタスクは長すぎるとみなす）
+pub fn lint(tasks: &[Task], max_time: Duration, calendar: &impl HolidayProvider) -> Vec<Finding> {
+    let mut findings = vec![];
+    let mut push = |t: &Task, severity, kind, message: String| {
+        findings.push(Finding {
+            severity,
+            kind,
+            row: t.row,
+            task_id: t.id.clone(),
+            task_name: t.name.clone(),
+            message,
+        })
+    };
+
+    for t in tasks {
+        if let (Some(b), Some(e)) = (t.begin_time, t.end_time) {
+            let timespan = e - b;
+            // 日をまたいだタスクは所要時間が長すぎるときだけ、終了時刻の誤りとみなす
+            if e.date() > b.date() && timespan > max_time {
+                push(
+                    t,
+                    Severity::Warning,
+                    FindingKind::EndBeforeBegin,
+                    format!(
+                        "終了時刻 {} が開始時刻 {} より前のため、日をまたいだものとして所要時間が{}分になっています",
+                        e.format("%H:%M"),
+                        b.format("%H:%M"),
+                        timespan.num_minutes()
+                    ),
+                );
+            } else if timespan > max_time {
+                push(
+                    t,
+                    Severity::Warning,
+                    FindingKind::TooLong,
+                    format!("所要時間が{}分あります", timespan.num_minutes()),
+                );
+            }
+            if timespan.is_zero() {
+                push(
+                    t,
+                    Severity::Info,
+                    FindingKind::ZeroLength,
+                    "所要時間が0分です".to_string(),
+                );
+            }
+        }
+        if t.estimated_time.map(|e| e.is_zero()).unwrap_or(false) {
+            push(
+                t,
+                Severity::Info,
+                FindingKind::ZeroEstimate,
+                "見積時間が0分です".to_string(),
+            );
+        }
+        if let Some(name) = calendar.holiday(&t.date, t.project.as_ref().map(|p| p.id.as_str())) {
+            push(
+                t,
+                Severity::Info,
+                FindingKind::Holiday,
+                format!("休日（{}）に実行されています", name),
+            );
+        }
+    }
+
+    for (id, group) in &tasks.iter().sorted_by_key(|t| &t.id).group_by(|t| &t.id) {
+        let group: Vec<_> = group.collect();
+        if group.len() < 2 {
+            continue;
+        }
+        let rows = group.iter().map(|t| t.row.to_string()).join(", ");
+        for t in group.iter() {
+            push(
+                t,
+                Severity::Error,
+                FindingKind::DuplicateId,
+                format!("タスクID {} が重複しています（{}行目）", id, rows),
+            );
+        }
+    }
+
+    // 開始時刻順に並べ、それまでで最も遅く終わるタスクと重なっていれば重複とみなす
+    let mut latest: Option<&Task> = None;
+    for t in tasks
+        .iter()
+        .filter(|t| t.begin_time.and(t.end_time).is_some())
+        .sorted_by_key(|t| t.begin_time)
+    {
+        if let Some(l) = latest {
+            if t.begin_time < l.end_time && t.end_time > l.begin_time {
+                push(
+                    t,
+                    Severity::Error,
+                    FindingKind::Overlap,
+                    format!(
+                        "{}行目の {} ({}) と時間が重なっています",
+                        l.row, l.name, l.id
+                    ),
+                );
+            }
+            if t.end_time > l.end_time {
+                latest = Some(t);
+            }
+        } else {
+            latest = Some(t);
+        }
+    }
+
+    findings.sort_by_key(|f| (f.row, f.severity));
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        holiday::HolidayCalendar,
+        test_util::{at, task},
+    };
+
+    /// 2020-06-01 のタスク（終了が開始より前なら翌日に終わる）
+    fn timed(row: u64, begin: (u32, u32), end: (u32, u32)) -> Task {
+        let end_day = if end < begin { 2 } else { 1 };
+        task(&row.to_string(), &format!("task{}", row))
+            .time(at(1, begin.0, begin.1), at(end_day, end.0, end.1))
+            .build()
+    }
+
+    fn kinds(tasks: &[Task]) -> Vec<(u64, FindingKind)> {
+        lint(tasks, Duration::hours(12), &HolidayCalendar::default())
+            .into_iter()
+            .map(|f| (f.row, f.kind))
+            .collect()
+    }
+
+    #[test]
+    fn adjacent_tasks_do_not_overlap() {
+        let tasks = [timed(1, (9, 0), (10, 0)), timed(2, (10, 0), (11, 0))];
+        assert_eq!(kinds(&tasks), vec![]);
+    }
+
+    #[test]
+    fn overlapping_tasks() {
+        let tasks = [timed(1, (9, 0), (10, 30)), timed(2, (10, 0), (11, 0))];
+        assert_eq!(kinds(&tasks), vec![(2, FindingKind::Overlap)]);
+    }
+
+    #[test]
+    fn task_across_midnight() {
+        assert_eq!(kinds(&[timed(1, (23, 0), (1, 0))]), vec![]);
+        assert_eq!(
+            kinds(&[timed(1, (10, 0), (9, 0))]),
+            vec![(1, FindingKind::EndBeforeBegin)]
+        );
+    }
+}
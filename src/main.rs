@@ -1,15 +1,31 @@
+use chrono::{Datelike, Duration, NaiveTime, Weekday};
 use clap::Clap;
+use lazy_static::lazy_static;
+use regex::Regex;
 use std::error::Error;
-use std::fs::File;
+use std::fs::{self, File};
 use std::{
-    collections::HashSet,
     fmt::{self, Display},
     io::stdout,
     io::Write,
     path::Path,
     str::FromStr,
 };
-use tcc::{analyze, csv_parser, markdown, Task};
+use tcc::{
+    compare, csv_parser, diff,
+    grouping::{GroupKey, Grouping},
+    holiday::{self, HolidayCalendar},
+    lint::lint,
+    markdown::{self, ReportOptions},
+    pivot::{self, Metric},
+    projects::{projects, ProjectSortKey, UNASSIGNED_ID},
+    remap::{Reassignment, Remapping},
+    routine::RoutineRules,
+    schema::{self, AnalysisReport, RawTaskRecord, TaskRecord},
+    unassigned::unassigned,
+    working_hours::WorkingHours,
+    DateRange, Query, SortKey, Task, TaskOrder,
+};
 
 fn main() -> Result<(), Box<dyn Error>> {
     App::parse().command.exec()
@@ -27,16 +43,54 @@ pub enum Command {
     /// Show project names and IDs
     #[clap(name = "project")]
     Project(ProjectCommand),
+    /// List tasks as parsed from the export
+    #[clap(name = "tasks")]
+    Tasks(TasksCommand),
     /// Extract tasks of a specified project and calculate used time
     #[clap(name = "analyze")]
     Analyze(AnalyzeCommand),
+    /// Compare two exports or two periods of one export
+    #[clap(name = "diff")]
+    Diff(DiffCommand),
+    /// Compare several projects side by side
+    #[clap(name = "compare")]
+    Compare(CompareCommand),
+    /// Show time logged without a project and suggest likely projects
+    #[clap(name = "unassigned")]
+    Unassigned(UnassignedCommand),
+    /// Cross-tabulate tasks by two dimensions
+    #[clap(name = "pivot")]
+    Pivot(PivotCommand),
+    /// Detect overlapping and inconsistent task records
+    #[clap(name = "lint")]
+    Lint(LintCommand),
+    /// Manage holiday data
+    #[clap(name = "holiday")]
+    Holiday(HolidayCommand),
+    /// Print the default markdown report template
+    #[clap(name = "template")]
+    Template,
+    /// Print the JSON Schema of `analyze --format json`
+    #[clap(name = "schema")]
+    Schema,
 }
 
 impl Command {
     fn exec(&self) -> Result<(), Box<dyn Error>> {
         match self {
             Command::Project(c) => c.exec()?,
+            Command::Tasks(c) => c.exec()?,
             Command::Analyze(c) => c.exec()?,
+            Command::Diff(c) => c.exec()?,
+            Command::Compare(c) => c.exec()?,
+            Command::Unassigned(c) => c.exec()?,
+            Command::Pivot(c) => c.exec()?,
+            Command::Lint(c) => c.exec()?,
+            Command::Holiday(c) => c.exec()?,
+            Command::Template => print!("{}", markdown::DEFAULT_TEMPLATE),
+            Command::Schema => {
+                println!("{}", serde_json::to_string_pretty(&schema::json_schema())?)
+            }
         };
         Ok(())
     }
@@ -46,18 +100,121 @@ impl Command {
 #[clap(name = "project")]
 pub struct ProjectCommand {
     file: String,
+    /// Order: id, name, tasks, work-time, first, last
+    #[clap(long, default_value = "id")]
+    sort: ProjectSortKey,
+    /// Sort in descending order
+    #[clap(long)]
+    desc: bool,
+    /// Include tasks without a project as "(none)"
+    #[clap(long)]
+    include_none: bool,
+    /// Format: table, csv, json
+    #[clap(short, long, default_value = "table", possible_values = Format::MARKDOWN_CSV_JSON)]
+    format: Format,
+    #[clap(flatten)]
+    holidays: HolidayOptions,
+    #[clap(flatten)]
+    remap: RemapOptions,
 }
 
 impl ProjectCommand {
     pub fn exec(&self) -> Result<(), Box<dyn Error>> {
-        let tasks = load(&self.file)?;
+        let calendar = self.holidays.calendar()?;
+        let (tasks, reassignments) = load(&self.file, &calendar, &self.remap)?;
+        if self.remap.dry_run(&reassignments)? {
+            return Ok(());
+        }
+        let res = projects(&tasks, self.include_none, self.sort, self.desc);
+
+        let out = stdout();
+        let mut stdout = out.lock();
+        match self.format {
+            Format::Markdown => markdown::write_projects_to(&mut stdout, &res)?,
+            Format::CSV => schema::write_csv_to(&mut stdout, &res)?,
+            Format::JSON => serde_json::to_writer(&mut stdout, &res)?,
+            other => return Err(other.unsupported()),
+        };
+        stdout.flush()?;
+        Ok(())
+    }
+}
 
-        let projects: HashSet<_> = tasks.into_iter().filter_map(|t| t.project).collect();
+#[derive(Debug, Clap)]
+#[clap(name = "tasks")]
+pub struct TasksCommand {
+    file: String,
+    /// Target project ID (all tasks if omitted)
+    #[clap(short, long)]
+    project: Option<String>,
+    /// Date range: YYYY-MM-DD..YYYY-MM-DD
+    #[clap(long)]
+    period: Option<DateRange>,
+    /// Only tasks whose name contains this text
+    #[clap(short, long)]
+    name: Option<String>,
+    /// Only tasks whose name matches this regular expression
+    #[clap(long)]
+    regex: Option<Regex>,
+    /// Only tasks with a non-zero estimate
+    #[clap(long)]
+    has_estimate: bool,
+    /// Only tasks that have not ended
+    #[clap(long)]
+    unfinished: bool,
+    /// Format: table, csv, json, ndjson
+    #[clap(short, long, default_value = "table", possible_values = Format::ALL)]
+    format: Format,
+    #[clap(flatten)]
+    holidays: HolidayOptions,
+    #[clap(flatten)]
+    remap: RemapOptions,
+}
 
-        projects
-            .into_iter()
-            .for_each(|p| println!("{} - {}", p.id, p.name));
+impl TasksCommand {
+    pub fn exec(&self) -> Result<(), Box<dyn Error>> {
+        let calendar = self.holidays.calendar()?;
+        let mut query = Query::new();
+        if let Some(id) = &self.project {
+            query = query.project(id);
+        }
+        if let Some(r) = self.period {
+            query = query.date_range(r);
+        }
+        if let Some(n) = &self.name {
+            query = query.filter(move |t| t.name.contains(n.as_str()));
+        }
+        if let Some(r) = &self.regex {
+            query = query.filter(move |t| r.is_match(&t.name));
+        }
+        if self.has_estimate {
+            query = query.filter(|t| t.estimated_time.map(|e| !e.is_zero()).unwrap_or(false));
+        }
+        if self.unfinished {
+            query = query.filter(|t| t.end_time.is_none());
+        }
+        let (tasks, reassignments) = load(&self.file, &calendar, &self.remap)?;
+        if self.remap.dry_run(&reassignments)? {
+            return Ok(());
+        }
+        let tasks = query.tasks(tasks);
 
+        let out = stdout();
+        let mut stdout = out.lock();
+        match self.format {
+            Format::Markdown => markdown::write_tasks_to(&mut stdout, &tasks)?,
+            Format::CSV => {
+                schema::write_csv_to(&mut stdout, tasks.iter().map(RawTaskRecord::from))?
+            }
+            Format::JSON => serde_json::to_writer(
+                &mut stdout,
+                &tasks.iter().map(RawTaskRecord::from).collect::<Vec<_>>(),
+            )?,
+            Format::NDJSON => {
+                schema::write_ndjson_to(&mut stdout, tasks.iter().map(RawTaskRecord::from))?
+            }
+        };
+        stdout.flush()?;
         Ok(())
     }
 }
@@ -66,21 +223,242 @@ impl ProjectCommand {
 #[clap(name = "analyze")]
 pub struct AnalyzeCommand {
     file: String,
+    /// Target project ID ('-' for tasks without a project)
+    #[clap(short, long)]
+    project: String,
+    /// Format: markdown, json, ndjson (one completed task per line)
+    #[clap(short, long, default_value = "markdown", possible_values = Format::MARKDOWN_JSON_NDJSON)]
+    format: Format,
+    /// Indent JSON output
+    #[clap(long)]
+    pretty: bool,
+    /// With --format ndjson, print the project's tasks as parsed, including unfinished ones
+    #[clap(long)]
+    raw: bool,
+    /// Value
+    #[clap(short, long)]
+    value: Option<i64>,
+    /// Breakdown to report, nested with '/': project, group, name, routine, tag, day, weekday, week, month, hour,
+    /// holiday-name, keyword=WORD,... (default: day, group and routine)
+    #[clap(short, long, number_of_values = 1)]
+    group_by: Vec<Grouping>,
+    /// Aggregate tasks repeated under the same name, ignoring trailing numbers and dates
+    #[clap(long)]
+    routines: bool,
+    /// Tasks separated by less than this many minutes form one session
+    #[clap(long, default_value = "5")]
+    session_gap: u32,
+    /// Print lint warnings of the project's tasks to stderr
+    #[clap(long)]
+    lint: bool,
+    /// Handlebars template for the markdown report (see `tcc template`)
+    #[clap(short, long)]
+    template: Option<String>,
+    /// Report sections to include: summary, routines, remaining, burnup, distribution, holidays,
+    /// working-hours, tasks and the --group-by breakdowns (e.g. day,group)
+    #[clap(long, require_delimiter = true)]
+    sections: Vec<String>,
+    /// Omit task tables from the report
+    #[clap(long)]
+    no_task_table: bool,
+    /// Show at most this many rows in each task table
+    #[clap(long)]
+    max_tasks: Option<usize>,
+    /// Order of task lists: timespan, gap, estimated, name, date
+    #[clap(long, default_value = "date")]
+    sort: SortKey,
+    /// Sort task lists in descending order
+    #[clap(long)]
+    desc: bool,
+    /// Show only the first N tasks of each task table after sorting (markdown only)
+    #[clap(long)]
+    top: Option<usize>,
+    #[clap(flatten)]
+    routine_rules: RoutineOptions,
+    #[clap(flatten)]
+    holidays: HolidayOptions,
+    #[clap(flatten)]
+    working_hours: WorkingHoursOptions,
+    #[clap(flatten)]
+    remap: RemapOptions,
+}
+
+impl AnalyzeCommand {
+    pub fn exec(&self) -> Result<(), Box<dyn Error>> {
+        let calendar = self.holidays.calendar()?;
+        let (tasks, reassignments) = load(&self.file, &calendar, &self.remap)?;
+        if self.remap.dry_run(&reassignments)? {
+            return Ok(());
+        }
+        let query = self.group_by.iter().fold(
+            Query::new()
+                .project(&self.project)
+                .value(self.value)
+                .routines(self.routines)
+                .routine_rules(self.routine_rules.rules())
+                .task_order(TaskOrder {
+                    key: self.sort,
+                    descending: self.desc,
+                })
+                .session_gap(self.session_gap)
+                .working_hours(self.working_hours.config())
+                .calendar(&calendar),
+            |q, g| q.grouping(g.clone()),
+        );
+        if self.lint {
+            for f in lint(
+                &query.tasks(tasks.clone()),
+                Duration::hours(LintCommand::DEFAULT_MAX_HOURS.parse()?),
+                &calendar,
+            ) {
+                eprintln!("{}", f);
+            }
+        }
+        if self.top.is_some() && self.format != Format::Markdown {
+            return Err("--top requires --format markdown".into());
+        }
+        if self.raw {
+            if self.format != Format::NDJSON {
+                return Err("--raw requires --format ndjson".into());
+            }
+            let tasks = query.tasks(tasks);
+            if tasks.is_empty() && self.project != UNASSIGNED_ID {
+                return Err("Project is not found.".into());
+            }
+            let out = stdout();
+            let mut stdout = out.lock();
+            schema::write_ndjson_to(&mut stdout, tasks.iter().map(RawTaskRecord::from))?;
+            stdout.flush()?;
+            return Ok(());
+        }
+        let res = query.analyze(tasks).ok_or("Project is not found.")?;
+
+        match self.format {
+            Format::JSON => {
+                let report = AnalysisReport::from(&res);
+                if self.pretty {
+                    serde_json::to_writer_pretty(stdout(), &report)?;
+                } else {
+                    serde_json::to_writer(stdout(), &report)?;
+                }
+            }
+            Format::NDJSON => {
+                let out = stdout();
+                let mut stdout = out.lock();
+                schema::write_ndjson_to(&mut stdout, res.all.tasks.iter().map(TaskRecord::from))?;
+                stdout.flush()?;
+            }
+            Format::Markdown => {
+                let out = stdout();
+                let mut stdout = out.lock();
+                markdown::write_report_to(
+                    &mut stdout,
+                    &res,
+                    &ReportOptions {
+                        template: self.template.as_ref().map(fs::read_to_string).transpose()?,
+                        sections: if self.sections.is_empty() {
+                            None
+                        } else {
+                            Some(self.sections.clone())
+                        },
+                        task_table: !self.no_task_table,
+                        max_tasks: self.max_tasks,
+                        top: self.top,
+                    },
+                )?;
+                stdout.flush()?;
+            }
+            other => return Err(other.unsupported()),
+        };
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clap)]
+#[clap(name = "diff")]
+pub struct DiffCommand {
+    /// Old and new files, or a single file with --before and --after
+    #[clap(required = true, max_values = 2)]
+    files: Vec<String>,
     /// Target project ID
     #[clap(short, long)]
     project: String,
+    /// Period of the old side: YYYY-MM-DD..YYYY-MM-DD
+    #[clap(long)]
+    before: Option<DateRange>,
+    /// Period of the new side: YYYY-MM-DD..YYYY-MM-DD
+    #[clap(long)]
+    after: Option<DateRange>,
     /// Format: markdown, json
-    #[clap(short, long, default_value = "markdown")]
+    #[clap(short, long, default_value = "markdown", possible_values = Format::MARKDOWN_JSON)]
     format: Format,
     /// Value
     #[clap(short, long)]
     value: Option<i64>,
+    /// Tasks separated by less than this many minutes form one session
+    #[clap(long, default_value = "5")]
+    session_gap: u32,
+    #[clap(flatten)]
+    holidays: HolidayOptions,
+    #[clap(flatten)]
+    working_hours: WorkingHoursOptions,
+    #[clap(flatten)]
+    remap: RemapOptions,
 }
 
-impl AnalyzeCommand {
+impl DiffCommand {
     pub fn exec(&self) -> Result<(), Box<dyn Error>> {
-        let tasks = load(&self.file)?;
-        let res = analyze(tasks, &self.project, self.value).expect("Project is not found.");
+        let calendar = self.holidays.calendar()?;
+        let (before, after) = match self.files.as_slice() {
+            [file] => {
+                if self.before.is_none() || self.after.is_none() {
+                    return Err("--before and --after are required to diff a single file".into());
+                }
+                let (tasks, reassignments) = load(file, &calendar, &self.remap)?;
+                if self.remap.dry_run(&reassignments)? {
+                    return Ok(());
+                }
+                (tasks.clone(), tasks)
+            }
+            [before, after] => {
+                let (before_tasks, before_reassignments) = load(before, &calendar, &self.remap)?;
+                let (after_tasks, after_reassignments) = load(after, &calendar, &self.remap)?;
+                if self.remap.dry_run {
+                    let out = stdout();
+                    let mut stdout = out.lock();
+                    for (file, r) in
+                        [(before, before_reassignments), (after, after_reassignments)].iter()
+                    {
+                        writeln!(stdout, "## {}\n", file)?;
+                        markdown::write_reassignments_to(&mut stdout, r)?;
+                        writeln!(stdout)?;
+                    }
+                    stdout.flush()?;
+                    return Ok(());
+                }
+                (before_tasks, after_tasks)
+            }
+            _ => unreachable!(),
+        };
+        let query = |range: Option<DateRange>| {
+            let query = Query::new()
+                .project(&self.project)
+                .value(self.value)
+                .session_gap(self.session_gap)
+                .working_hours(self.working_hours.config())
+                .calendar(&calendar);
+            match range {
+                Some(r) => query.date_range(r),
+                None => query,
+            }
+        };
+        let before = query(self.before)
+            .analyze(before)
+            .ok_or("Project is not found.")?;
+        let after = query(self.after)
+            .analyze(after)
+            .ok_or("Project is not found.")?;
+        let res = diff(&before, &after);
 
         match self.format {
             Format::JSON => {
@@ -89,18 +467,325 @@ impl AnalyzeCommand {
             Format::Markdown => {
                 let out = stdout();
                 let mut stdout = out.lock();
-                markdown::write_to(&mut stdout, &res)?;
+                markdown::write_diff_to(&mut stdout, &res)?;
                 stdout.flush()?;
             }
+            other => return Err(other.unsupported()),
         };
         Ok(())
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clap)]
+#[clap(name = "compare")]
+pub struct CompareCommand {
+    file: String,
+    /// Target project IDs
+    #[clap(short, long, required = true, number_of_values = 1)]
+    project: Vec<String>,
+    /// Format: markdown, json
+    #[clap(short, long, default_value = "markdown", possible_values = Format::MARKDOWN_JSON)]
+    format: Format,
+    /// Values for each project, in the same order as --project
+    #[clap(short, long, number_of_values = 1)]
+    value: Vec<i64>,
+    /// Tasks separated by less than this many minutes form one session
+    #[clap(long, default_value = "5")]
+    session_gap: u32,
+    #[clap(flatten)]
+    holidays: HolidayOptions,
+    #[clap(flatten)]
+    working_hours: WorkingHoursOptions,
+    #[clap(flatten)]
+    remap: RemapOptions,
+}
+
+impl CompareCommand {
+    pub fn exec(&self) -> Result<(), Box<dyn Error>> {
+        let calendar = self.holidays.calendar()?;
+        let (tasks, reassignments) = load(&self.file, &calendar, &self.remap)?;
+        if self.remap.dry_run(&reassignments)? {
+            return Ok(());
+        }
+        let results = self
+            .project
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                Query::new()
+                    .project(p)
+                    .value(self.value.get(i).copied())
+                    .session_gap(self.session_gap)
+                    .working_hours(self.working_hours.config())
+                    .calendar(&calendar)
+                    .analyze(tasks.clone())
+                    .ok_or_else(|| format!("Project {} is not found.", p))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let res = compare(&results);
+
+        match self.format {
+            Format::JSON => {
+                serde_json::to_writer(stdout(), &res)?;
+            }
+            Format::Markdown => {
+                let out = stdout();
+                let mut stdout = out.lock();
+                markdown::write_compare_to(&mut stdout, &res)?;
+                stdout.flush()?;
+            }
+            other => return Err(other.unsupported()),
+        };
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clap)]
+#[clap(name = "unassigned")]
+pub struct UnassignedCommand {
+    file: String,
+    /// Format: markdown, json
+    #[clap(short, long, default_value = "markdown", possible_values = Format::MARKDOWN_JSON)]
+    format: Format,
+    #[clap(flatten)]
+    holidays: HolidayOptions,
+    #[clap(flatten)]
+    remap: RemapOptions,
+}
+
+impl UnassignedCommand {
+    pub fn exec(&self) -> Result<(), Box<dyn Error>> {
+        let calendar = self.holidays.calendar()?;
+        let (tasks, reassignments) = load(&self.file, &calendar, &self.remap)?;
+        if self.remap.dry_run(&reassignments)? {
+            return Ok(());
+        }
+        let res = unassigned(&tasks);
+
+        match self.format {
+            Format::JSON => {
+                serde_json::to_writer(stdout(), &res)?;
+            }
+            Format::Markdown => {
+                let out = stdout();
+                let mut stdout = out.lock();
+                markdown::write_unassigned_to(&mut stdout, &res)?;
+                stdout.flush()?;
+            }
+            other => return Err(other.unsupported()),
+        };
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clap)]
+#[clap(name = "pivot")]
+pub struct PivotCommand {
+    file: String,
+    /// Target project ID (all tasks if omitted)
+    #[clap(short, long)]
+    project: Option<String>,
+    /// Row dimension: project, group, name, routine, tag, day, weekday, week, month, hour, holiday-name,
+    /// keyword=WORD,...
+    #[clap(long)]
+    rows: GroupKey,
+    /// Column dimension, same as --rows
+    #[clap(long)]
+    cols: GroupKey,
+    /// Metric: work_time, estimated_time, count, gap_ratio
+    #[clap(short, long, default_value = "work_time")]
+    metric: Metric,
+    /// Format: markdown, csv, json
+    #[clap(short, long, default_value = "markdown", possible_values = Format::MARKDOWN_CSV_JSON)]
+    format: Format,
+    #[clap(flatten)]
+    routine_rules: RoutineOptions,
+    #[clap(flatten)]
+    holidays: HolidayOptions,
+    #[clap(flatten)]
+    remap: RemapOptions,
+}
+
+impl PivotCommand {
+    pub fn exec(&self) -> Result<(), Box<dyn Error>> {
+        let calendar = self.holidays.calendar()?;
+        let (tasks, reassignments) = load(&self.file, &calendar, &self.remap)?;
+        if self.remap.dry_run(&reassignments)? {
+            return Ok(());
+        }
+        let query = match &self.project {
+            Some(id) => Query::new().project(id),
+            None => Query::new(),
+        }
+        .routine_rules(self.routine_rules.rules());
+        let res = query
+            .pivot(tasks, &self.rows, &self.cols, self.metric)
+            .ok_or("Project is not found.")?;
+
+        let out = stdout();
+        let mut stdout = out.lock();
+        match self.format {
+            Format::JSON => {
+                serde_json::to_writer(&mut stdout, &res)?;
+            }
+            Format::CSV => {
+                pivot::write_csv_to(&mut stdout, &res)?;
+            }
+            Format::Markdown => {
+                markdown::write_pivot_to(&mut stdout, &res)?;
+            }
+            other => return Err(other.unsupported()),
+        };
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clap)]
+#[clap(name = "lint")]
+pub struct LintCommand {
+    file: String,
+    /// Target project ID (all tasks if omitted)
+    #[clap(short, long)]
+    project: Option<String>,
+    /// Tasks longer than this many hours are reported
+    #[clap(long, default_value = LintCommand::DEFAULT_MAX_HOURS)]
+    max_hours: i64,
+    /// Format: markdown, json
+    #[clap(short, long, default_value = "markdown", possible_values = Format::MARKDOWN_JSON)]
+    format: Format,
+    #[clap(flatten)]
+    holidays: HolidayOptions,
+    #[clap(flatten)]
+    remap: RemapOptions,
+}
+
+impl LintCommand {
+    /// `--max-hours` の既定値（`analyze --lint` でも使う）
+    const DEFAULT_MAX_HOURS: &'static str = "12";
+
+    pub fn exec(&self) -> Result<(), Box<dyn Error>> {
+        let calendar = self.holidays.calendar()?;
+        let query = match &self.project {
+            Some(id) => Query::new().project(id),
+            None => Query::new(),
+        };
+        let (tasks, reassignments) = load(&self.file, &calendar, &self.remap)?;
+        if self.remap.dry_run(&reassignments)? {
+            return Ok(());
+        }
+        let tasks = query.tasks(tasks);
+        let res = lint(&tasks, Duration::hours(self.max_hours), &calendar);
+
+        match self.format {
+            Format::JSON => {
+                serde_json::to_writer(stdout(), &res)?;
+            }
+            Format::Markdown => {
+                let out = stdout();
+                let mut stdout = out.lock();
+                markdown::write_lint_to(&mut stdout, &res)?;
+                stdout.flush()?;
+            }
+            other => return Err(other.unsupported()),
+        };
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clap)]
+#[clap(name = "holiday")]
+pub struct HolidayCommand {
+    #[clap(subcommand)]
+    command: HolidaySubcommand,
+}
+
+#[derive(Debug, Clap)]
+pub enum HolidaySubcommand {
+    /// Store a newer syukujitsu.csv from the Cabinet Office in the user data directory
+    #[clap(name = "update")]
+    Update(HolidayUpdateCommand),
+    /// Show the loaded holidays
+    #[clap(name = "list")]
+    List(HolidayListCommand),
+}
+
+impl HolidayCommand {
+    pub fn exec(&self) -> Result<(), Box<dyn Error>> {
+        match &self.command {
+            HolidaySubcommand::Update(c) => c.exec()?,
+            HolidaySubcommand::List(c) => c.exec()?,
+        };
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clap)]
+#[clap(name = "update")]
+pub struct HolidayUpdateCommand {
+    /// syukujitsu.csv (Shift_JIS) downloaded from https://www8.cao.go.jp/chosei/shukujitsu/syukujitsu.csv
+    #[clap(long)]
+    from: String,
+}
+
+impl HolidayUpdateCommand {
+    pub fn exec(&self) -> Result<(), Box<dyn Error>> {
+        let (path, count) = holiday::update(&fs::read(&self.from)?)?;
+        println!("Saved {} holidays to {}", count, path.display());
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clap)]
+#[clap(name = "list")]
+pub struct HolidayListCommand {
+    /// Year to show
+    #[clap(short, long)]
+    year: Option<i32>,
+    /// Apply holidays specific to this project ID
+    #[clap(short, long)]
+    project: Option<String>,
+    #[clap(flatten)]
+    holidays: HolidayOptions,
+}
+
+impl HolidayListCommand {
+    pub fn exec(&self) -> Result<(), Box<dyn Error>> {
+        let calendar = self.holidays.calendar()?;
+
+        if let Some((begin, end)) = calendar.coverage() {
+            eprintln!("Holiday data: {} - {}", begin, end);
+        }
+        calendar
+            .list(self.project.as_deref())
+            .into_iter()
+            .filter(|(d, _)| self.year.map(|y| d.year() == y).unwrap_or(true))
+            .for_each(|(d, n)| println!("{} {}", d, n));
+
+        Ok(())
+    }
+}
+
+/// 出力の形式（コマンドごとに対応している形式は異なる）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Format {
     Markdown,
+    CSV,
     JSON,
+    NDJSON,
+}
+
+impl Format {
+    // `--format` に指定できる値（別名を含む）。コマンドごとに対応している形式だけを受け付ける
+    const ALL: &'static [&'static str] = &["markdown", "md", "table", "csv", "json", "ndjson"];
+    const MARKDOWN_JSON: &'static [&'static str] = &["markdown", "md", "table", "json"];
+    const MARKDOWN_CSV_JSON: &'static [&'static str] = &["markdown", "md", "table", "csv", "json"];
+    const MARKDOWN_JSON_NDJSON: &'static [&'static str] =
+        &["markdown", "md", "table", "json", "ndjson"];
+
+    fn unsupported(self) -> Box<dyn Error> {
+        format!("--format {} is not supported by this command", self).into()
+    }
 }
 
 impl Display for Format {
@@ -109,8 +794,10 @@ impl Display for Format {
             f,
             "{}",
             match self {
-                &Self::Markdown => "markdown",
-                &Self::JSON => "json",
+                Self::Markdown => "markdown",
+                Self::CSV => "csv",
+                Self::JSON => "json",
+                Self::NDJSON => "ndjson",
             }
         )
     }
@@ -121,14 +808,280 @@ impl FromStr for Format {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "markdown" | "md" => Ok(Self::Markdown),
+            "markdown" | "md" | "table" => Ok(Self::Markdown),
+            "csv" => Ok(Self::CSV),
             "json" => Ok(Self::JSON),
+            "ndjson" => Ok(Self::NDJSON),
             _ => Err("invalid format"),
         }
     }
 }
 
-fn load<P: AsRef<Path>>(file_name: P) -> Result<Vec<Task>, Box<dyn Error>> {
+#[derive(Debug, Clap)]
+pub struct HolidayOptions {
+    /// Holiday CSV (date,name,project,person) or ICS file merged over the built-in holidays
+    #[clap(long = "holidays", number_of_values = 1)]
+    holiday_files: Vec<String>,
+    /// Holiday CSV or ICS file applied only to one project: PROJECT=FILE
+    #[clap(long = "project-holidays", number_of_values = 1)]
+    project_holiday_files: Vec<ProjectFile>,
+    /// Apply the rows of the holiday CSV files whose person column is this name
+    #[clap(long)]
+    person: Option<String>,
+    /// Do not use the built-in Japanese holidays
+    #[clap(long)]
+    no_builtin_holidays: bool,
+}
+
+impl HolidayOptions {
+    fn calendar(&self) -> Result<HolidayCalendar, Box<dyn Error>> {
+        let mut calendar = if self.no_builtin_holidays {
+            HolidayCalendar::default()
+        } else {
+            HolidayCalendar::builtin()
+        };
+        for f in self.holiday_files.iter() {
+            calendar.merge(HolidayCalendar::load(f)?.for_person(self.person.as_deref()));
+        }
+        for f in self.project_holiday_files.iter() {
+            calendar.merge(
+                HolidayCalendar::load(&f.file)?
+                    .for_person(self.person.as_deref())
+                    .for_project(&f.project),
+            );
+        }
+        Ok(calendar)
+    }
+}
+
+#[derive(Debug)]
+pub struct ProjectFile {
+    project: String,
+    file: String,
+}
+
+impl FromStr for ProjectFile {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.find('=') {
+            Some(i) if i > 0 && i + 1 < s.len() => Ok(Self {
+                project: s[..i].to_string(),
+                file: s[i + 1..].to_string(),
+            }),
+            _ => Err("expected PROJECT=FILE"),
+        }
+    }
+}
+
+#[derive(Debug, Clap)]
+pub struct RemapOptions {
+    /// Rules CSV (name,comment,period,project_id,project_name,override) reassigning tasks to projects
+    #[clap(long = "remap")]
+    remap_file: Option<String>,
+    /// List the tasks the --remap rules reassign instead of running the command
+    #[clap(long, requires = "remap-file")]
+    dry_run: bool,
+}
+
+impl RemapOptions {
+    fn apply(
+        &self,
+        tasks: &mut [Task],
+        calendar: &HolidayCalendar,
+    ) -> Result<Vec<Reassignment>, Box<dyn Error>> {
+        let res = match &self.remap_file {
+            Some(f) => Remapping::load(f)?.apply(tasks),
+            None => return Ok(vec![]),
+        };
+        csv_parser::resolve_holidays(tasks, calendar);
+        Ok(res)
+    }
+
+    /// `--dry-run` のとき、付け替えたタスクの一覧を出力する（出力したらコマンドは何もしない）
+    fn dry_run(&self, reassignments: &[Reassignment]) -> Result<bool, Box<dyn Error>> {
+        if !self.dry_run {
+            return Ok(false);
+        }
+        let out = stdout();
+        let mut stdout = out.lock();
+        markdown::write_reassignments_to(&mut stdout, reassignments)?;
+        stdout.flush()?;
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Clap)]
+pub struct RoutineOptions {
+    /// Tasks whose name recurs on at least this many days are routines
+    #[clap(long, default_value = "3")]
+    routine_days: usize,
+    /// Task name always treated as a routine
+    #[clap(long = "routine", number_of_values = 1)]
+    routine_names: Vec<String>,
+    /// Task name always treated as one-off work
+    #[clap(long = "one-off", number_of_values = 1)]
+    one_off_names: Vec<String>,
+}
+
+impl RoutineOptions {
+    fn rules(&self) -> RoutineRules {
+        RoutineRules {
+            min_days: self.routine_days,
+            routines: self.routine_names.clone(),
+            one_offs: self.one_off_names.clone(),
+        }
+    }
+}
+
+lazy_static! {
+    /// `WorkingHoursOptions` の既定値（`WorkingHours::default` から作る）
+    static ref WORKING_HOURS_DEFAULTS: [String; 5] = {
+        let d = WorkingHours::default();
+        [
+            format!("{}-{}", d.begin.format("%H:%M"), d.end.format("%H:%M")),
+            d.break_time.to_string(),
+            (d.daily_limit / 60).to_string(),
+            (d.weekly_limit / 60).to_string(),
+            format!("{:?}", d.rest_day),
+        ]
+    };
+}
+
+#[derive(Debug, Clap)]
+pub struct WorkingHoursOptions {
+    /// Standard working hours: HH:MM-HH:MM
+    #[clap(long, default_value = WORKING_HOURS_DEFAULTS[0].as_str())]
+    work_hours: TimeRange,
+    /// Break time in minutes within the standard working hours
+    #[clap(long, default_value = WORKING_HOURS_DEFAULTS[1].as_str())]
+    break_time: i64,
+    /// Statutory working hours per day
+    #[clap(long, default_value = WORKING_HOURS_DEFAULTS[2].as_str())]
+    daily_limit: i64,
+    /// Statutory working hours per week
+    #[clap(long, default_value = WORKING_HOURS_DEFAULTS[3].as_str())]
+    weekly_limit: i64,
+    /// Statutory rest day, whose work is not counted toward the weekly limit: Mon, ..., Sun
+    #[clap(long, default_value = WORKING_HOURS_DEFAULTS[4].as_str())]
+    rest_day: RestDay,
+}
+
+impl WorkingHoursOptions {
+    fn config(&self) -> WorkingHours {
+        WorkingHours {
+            begin: self.work_hours.0,
+            end: self.work_hours.1,
+            break_time: self.break_time,
+            daily_limit: self.daily_limit * 60,
+            weekly_limit: self.weekly_limit * 60,
+            rest_day: self.rest_day.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RestDay(Weekday);
+
+impl FromStr for RestDay {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self).map_err(|_| "invalid weekday")
+    }
+}
+
+#[derive(Debug)]
+pub struct TimeRange(NaiveTime, NaiveTime);
+
+impl FromStr for TimeRange {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut it = s
+            .splitn(2, '-')
+            .map(|t| NaiveTime::parse_from_str(t.trim(), "%H:%M"));
+        match (it.next(), it.next()) {
+            (Some(Ok(b)), Some(Ok(e))) if b < e => Ok(Self(b, e)),
+            _ => Err("invalid time range"),
+        }
+    }
+}
+
+fn load<P: AsRef<Path>>(
+    file_name: P,
+    calendar: &HolidayCalendar,
+    remap: &RemapOptions,
+) -> Result<(Vec<Task>, Vec<Reassignment>), Box<dyn Error>> {
     let file = File::open(file_name)?;
-    Ok(csv_parser::parse(&file))
+    let mut tasks = csv_parser::parse_with(&file, calendar);
+    let reassignments = remap.apply(&mut tasks, calendar)?;
+
+    if let Some((begin, end)) = calendar.coverage() {
+        let outside: Vec<_> = tasks
+            .iter()
+            .map(|t| t.date)
+            .filter(|d| *d < begin || end < *d)
+            .collect();
+        if let (Some(min), Some(max)) = (outside.iter().min(), outside.iter().max()) {
+            eprintln!(
+                "warning: {} - {} is outside the holiday data ({} - {}). Run `tcc holiday update` to load newer data.",
+                min, max, begin, end
+            );
+        }
+    }
+
+    Ok((tasks, reassignments))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Command, String> {
+        App::try_parse_from(format!("tcc {}", args).split_whitespace())
+            .map(|a| a.command)
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn commands_accept_only_their_formats() {
+        for args in [
+            "project -f csv t.tsv",
+            "tasks -f ndjson t.tsv",
+            "analyze -p 1 -f ndjson t.tsv",
+            "diff -p 1 -f json a.tsv b.tsv",
+            "pivot --rows group --cols month -f csv t.tsv",
+            "unassigned -f md t.tsv",
+        ]
+        .iter()
+        {
+            assert!(parse(args).is_ok(), "{}", args);
+        }
+
+        for args in [
+            "project -f ndjson t.tsv",
+            "analyze -p 1 -f csv t.tsv",
+            "diff -p 1 -f csv a.tsv b.tsv",
+            "compare -p 1 -p 2 -f ndjson t.tsv",
+            "unassigned -f csv t.tsv",
+            "lint -f ndjson t.tsv",
+            "tasks -f xml t.tsv",
+        ]
+        .iter()
+        {
+            let err = parse(args).unwrap_err();
+            assert!(err.contains("--format"), "{}: {}", args, err);
+        }
+    }
+
+    #[test]
+    fn format_aliases_are_markdown() {
+        for alias in ["markdown", "md", "table"].iter() {
+            match parse(&format!("analyze -p 1 -f {} t.tsv", alias)) {
+                Ok(Command::Analyze(c)) => assert_eq!(c.format, Format::Markdown),
+                other => panic!("{:?}", other),
+            }
+        }
+    }
 }
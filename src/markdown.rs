@@ -1,153 +1,584 @@
-use crate::{analyzer::TasksAnalysisResult, AnalysisResult, AnalysisResultTask};
-use chrono::Duration;
+use crate::{
+    compare::{CompareMetrics, CompareResult},
+    diff::{Delta, DiffResult, DiffTask, TasksDiff},
+    grouping::Grouping,
+    lint::Finding,
+    pivot::PivotResult,
+    projects::ProjectSummary,
+    remap::Reassignment,
+    unassigned::UnassignedResult,
+    AnalysisResult, Project, Task,
+};
+use chrono::{Duration, NaiveDate, NaiveDateTime, Weekday};
+use handlebars::{handlebars_helper, Handlebars};
+use itertools::Itertools;
+use serde_json::json;
 use std::{
     error::Error,
     fmt::{self, Display},
     io::Write,
 };
 
+/// 既定のレポートのテンプレート（Handlebars）
+pub const DEFAULT_TEMPLATE: &str = include_str!("report.md.hbs");
+
+/// `--sections` に指定できる、分類以外のセクション
+pub const SECTIONS: [&str; 8] = [
+    "summary",
+    "routines",
+    "remaining",
+    "burnup",
+    "distribution",
+    "holidays",
+    "working-hours",
+    "tasks",
+];
+
+/// レポートに出力する内容
+#[derive(Debug, Clone)]
+pub struct ReportOptions {
+    /// Handlebarsのテンプレート（なければ既定のテンプレート）
+    pub template: Option<String>,
+    /// 出力するセクション（[`SECTIONS`] か `month/group` のような分類の観点、なければすべて）
+    pub sections: Option<Vec<String>>,
+    /// タスクの一覧を出力する
+    pub task_table: bool,
+    /// タスクの一覧ごとに出力する最大の件数
+    pub max_tasks: Option<usize>,
+    /// タスクの一覧ごとに、並べ替えた上位のこの件数だけを出力する（`max_tasks` と小さい方を使う）
+    pub top: Option<usize>,
+}
+
+impl Default for ReportOptions {
+    fn default() -> Self {
+        Self {
+            template: None,
+            sections: None,
+            task_table: true,
+            max_tasks: None,
+            top: None,
+        }
+    }
+}
+
 pub fn write_to<W: Write>(w: &mut W, v: &AnalysisResult) -> Result<(), Box<dyn Error>> {
+    write_report_to(w, v, &ReportOptions::default())
+}
+
+/// 分析結果をテンプレートに埋め込んで出力する
+///
+/// テンプレートからは分析結果のJSONと同じ名前で値を参照できるほか、`sections` に出力するセクション、
+/// `options` にタスクの一覧の設定（`task_table`・`max_tasks`、`max_tasks` には `top` も反映する）が入っている。
+/// ヘルパーは値の書式を整えるものだけを用意している（[`register_helpers`]）。
+pub fn write_report_to<W: Write>(
+    w: &mut W,
+    v: &AnalysisResult,
+    options: &ReportOptions,
+) -> Result<(), Box<dyn Error>> {
+    let groupings: Vec<_> = v.groups.iter().map(|g| g.grouping.to_string()).collect();
+    if let Some(s) = options
+        .sections
+        .iter()
+        .flatten()
+        .find(|s| !SECTIONS.contains(&s.as_str()) && !groupings.contains(s))
+    {
+        return Err(format!("unknown section: {}", s).into());
+    }
+    let shown = |name: &str| {
+        options
+            .sections
+            .as_ref()
+            .map(|s| s.iter().any(|s| s == name))
+            .unwrap_or(true)
+    };
+
+    let mut hb = Handlebars::new();
+    hb.register_escape_fn(handlebars::no_escape);
+    register_helpers(&mut hb);
+
+    let mut data = serde_json::to_value(v)?;
+    data["sections"] = json!({
+        "summary": shown("summary"),
+        "groups": groupings.iter().map(|g| shown(g)).collect::<Vec<_>>(),
+        "routines": shown("routines"),
+        "remaining": shown("remaining"),
+        "burnup": shown("burnup"),
+        "distribution": shown("distribution"),
+        "holidays": shown("holidays"),
+        "working_hours": shown("working-hours"),
+        "tasks": shown("tasks"),
+    });
+    data["options"] = json!({
+        "task_table": options.task_table,
+        "max_tasks": options.max_tasks.into_iter().chain(options.top).min(),
+    });
+
+    let template = options.template.as_deref().unwrap_or(DEFAULT_TEMPLATE);
+    w.write_all(hb.render_template(template, &data)?.as_bytes())?;
+    Ok(())
+}
+
+/// テンプレートで値の書式を整えるヘルパー
+///
+/// - `timespan 分`: `1h30m (1.5h) = 90m` の形
+/// - `ratio 値`・`fixed 値 桁数`・`signed 値 桁数`: 小数（`ratio` は2桁、`signed` は符号つき）
+/// - `hours 分`: 時間（小数2桁）
+///
+/// 数値のヘルパーは、値がない（未完了のタスクだけのときの `NaN` など）と `-` にする。
+/// - `date 日付 書式`: `%Y-%m-%d` のような chrono の書式（値がなければ `-`）
+/// - `opt 値`: 値がなければ `-`
+/// - `weekday 曜日`: `Mon` を `月` に
+/// - `label 観点`: `month/group` のような観点を `月・工程` に
+/// - `heading 深さ`: 見出しの `#`（6つまで）
+/// - `bar 値 行`: `[キー, 値]` の行の最大値に対する棒グラフ
+/// - `len 配列`・`add 数 数`・`sub 数 数`: 件数・見出しの深さの計算
+fn register_helpers(hb: &mut Handlebars) {
+    fn number(v: &serde_json::Value, f: impl Fn(f64) -> String) -> String {
+        v.as_f64()
+            .filter(|v| v.is_finite())
+            .map(f)
+            .unwrap_or_else(|| "-".into())
+    }
+
+    handlebars_helper!(timespan: |m: Json| number(m, |m| Timespan::from(m).to_string()));
+    handlebars_helper!(ratio: |r: Json| number(r, |r| format!("{:.2}", r)));
+    handlebars_helper!(fixed: |v: Json, digits: u64| number(v, |v| format!("{:.*}", digits as usize, v)));
+    handlebars_helper!(signed: |v: Json, digits: u64| number(v, |v| format!("{:+.*}", digits as usize, v)));
+    handlebars_helper!(hours: |m: Json| number(m, |m| format!("{:.2}", m / 60f64)));
+    handlebars_helper!(date: |v: Json, format: str| {
+        let s = v.as_str().unwrap_or_default();
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S")
+            .or_else(|_| NaiveDate::parse_from_str(s, "%Y-%m-%d").map(|d| d.and_hms(0, 0, 0)))
+            .map(|d| d.format(format).to_string())
+            .unwrap_or_else(|_| "-".into())
+    });
+    handlebars_helper!(opt: |v: Json| match v {
+        serde_json::Value::Null => "-".to_string(),
+        serde_json::Value::String(s) => s.clone(),
+        v => v.to_string(),
+    });
+    handlebars_helper!(weekday: |w: str| match w.parse::<Weekday>() {
+        Ok(Weekday::Mon) => "月",
+        Ok(Weekday::Tue) => "火",
+        Ok(Weekday::Wed) => "水",
+        Ok(Weekday::Thu) => "木",
+        Ok(Weekday::Fri) => "金",
+        Ok(Weekday::Sat) => "土",
+        Ok(Weekday::Sun) => "日",
+        Err(_) => w,
+    }.to_string());
+    handlebars_helper!(label: |keys: Json| {
+        let keys: Vec<_> = keys.as_array().into_iter().flatten().filter_map(|k| k.as_str()).collect();
+        keys.join("/").parse::<Grouping>().map(|g| g.label()).unwrap_or_default()
+    });
+    handlebars_helper!(heading: |depth: u64| "#".repeat((depth as usize).min(6)));
+    handlebars_helper!(bar: |v: i64, rows: Json| {
+        let max = rows
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|r| r.get(1).and_then(|v| v.as_i64()))
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        "█".repeat((v * 20 / max) as usize)
+    });
+    handlebars_helper!(len: |v: Json| v.as_array().map(|a| a.len()).unwrap_or(0));
+    handlebars_helper!(add: |a: i64, b: i64| a + b);
+    handlebars_helper!(sub: |a: i64, b: i64| a - b);
+
+    hb.register_helper("timespan", Box::new(timespan));
+    hb.register_helper("ratio", Box::new(ratio));
+    hb.register_helper("fixed", Box::new(fixed));
+    hb.register_helper("signed", Box::new(signed));
+    hb.register_helper("hours", Box::new(hours));
+    hb.register_helper("date", Box::new(date));
+    hb.register_helper("opt", Box::new(opt));
+    hb.register_helper("weekday", Box::new(weekday));
+    hb.register_helper("label", Box::new(label));
+    hb.register_helper("heading", Box::new(heading));
+    hb.register_helper("bar", Box::new(bar));
+    hb.register_helper("len", Box::new(len));
+    hb.register_helper("add", Box::new(add));
+    hb.register_helper("sub", Box::new(sub));
+}
+
+pub fn write_diff_to<W: Write>(w: &mut W, v: &DiffResult) -> Result<(), Box<dyn Error>> {
     write!(
         w,
-        r#"# {name}
+        r#"# {name} の差分
 
 ## 全タスク
 
 {all}
-
-## 平日休日別
-
-{day}
 ## 工程別
 
-{group}
-## 全タスクの一覧
+{group}## 追加されたタスク
 
-{tasktable}"#,
+{added}
+## 削除されたタスク
+
+{removed}"#,
         name = v.project_name,
-        all = Analysis(&v.all, v.value),
-        day = Group(&v.day, v.value),
-        group = Group(&v.group, v.value),
-        tasktable = TaskTable(&v.all.tasks),
+        all = DiffTable(&v.all),
+        group = v
+            .group
+            .iter()
+            .map(|(k, d)| format!("### {}\n\n{}\n", k, DiffTable(d)))
+            .join(""),
+        added = DiffTaskList(&v.added),
+        removed = DiffTaskList(&v.removed),
     )?;
 
     Ok(())
 }
 
-struct TaskTable<'a>(&'a [AnalysisResultTask]);
+pub fn write_lint_to<W: Write>(w: &mut W, v: &[Finding]) -> Result<(), Box<dyn Error>> {
+    writeln!(w, "|重要度|行|タスクID|タスク|内容|\n|---|---|---|---|---|")?;
+    for f in v {
+        writeln!(
+            w,
+            "|{}|{}|{}|{}|{}|",
+            f.severity, f.row, f.task_id, f.task_name, f.message
+        )?;
+    }
 
-impl<'a> TaskTable<'a> {
-    const HEADERS: [&'static str; 8] = [
-        "タスク",
-        "日付",
-        "開始時刻",
-        "終了時刻",
-        "予定",
-        "実績",
-        "実績/予定",
-        "コメント",
-    ];
+    Ok(())
 }
 
-impl<'a> Display for TaskTable<'a> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "|{}|\n|{}|\n",
-            Self::HEADERS.join("|"),
-            Self::HEADERS
-                .iter()
-                .map(|_| "---")
-                .collect::<Vec<_>>()
-                .join("|")
+pub fn write_projects_to<W: Write>(w: &mut W, v: &[ProjectSummary]) -> Result<(), Box<dyn Error>> {
+    writeln!(
+        w,
+        "|プロジェクトID|プロジェクト|タスク数|所要時間|割合|最初|最後|\n|---|---|---|---|---|---|---|"
+    )?;
+    let date = |d: Option<NaiveDate>| {
+        d.map(|d| d.format("%Y-%m-%d").to_string())
+            .unwrap_or_else(|| "-".into())
+    };
+    for p in v {
+        writeln!(
+            w,
+            "|{}|{}|{}|{}|{}|{}|{}|",
+            p.id.as_deref().unwrap_or("-"),
+            p.name,
+            p.tasks,
+            Timespan::from(p.work_time),
+            p.share
+                .map(|s| format!("{:.1}%", s * 100.0))
+                .unwrap_or_else(|| "-".into()),
+            date(p.first_date),
+            date(p.last_date),
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn write_unassigned_to<W: Write>(
+    w: &mut W,
+    v: &UnassignedResult,
+) -> Result<(), Box<dyn Error>> {
+    writeln!(
+        w,
+        "# プロジェクトなしのタスク\n\n- 全体の所要時間： {}\n- プロジェクトなしの所要時間： {}{}\n- タスク数： {}\n",
+        Timespan::from(v.total_work_time),
+        Timespan::from(v.work_time),
+        v.share
+            .map(|s| format!(" ({:.1}%)", s * 100.0))
+            .unwrap_or_default(),
+        v.tasks,
+    )?;
+    writeln!(
+        w,
+        "|タスク|回数|所要時間|推定プロジェクト|類似度|似ているタスク|\n|---|---|---|---|---|---|"
+    )?;
+    for n in &v.names {
+        let (project, similarity, task) = match &n.suggestion {
+            Some(s) => (
+                format!("{} - {}", s.project.id, s.project.name),
+                format!("{:.2}", s.similarity),
+                s.similar_task.clone(),
+            ),
+            None => ("-".into(), "-".into(), "-".into()),
+        };
+        writeln!(
+            w,
+            "|{}|{}|{}|{}|{}|{}|",
+            n.name,
+            n.count,
+            Timespan::from(n.work_time),
+            project,
+            similarity,
+            task
         )?;
+    }
+
+    Ok(())
+}
+
+pub fn write_reassignments_to<W: Write>(
+    w: &mut W,
+    v: &[Reassignment],
+) -> Result<(), Box<dyn Error>> {
+    writeln!(
+        w,
+        "|行|タスクID|タスク|変更前|変更後|規則の行|\n|---|---|---|---|---|---|"
+    )?;
+    let project = |p: &Project| format!("{} - {}", p.id, p.name);
+    for r in v {
+        writeln!(
+            w,
+            "|{}|{}|{}|{}|{}|{}|",
+            r.row,
+            r.task_id,
+            r.task_name,
+            r.from.as_ref().map(project).unwrap_or_else(|| "-".into()),
+            project(&r.to),
+            r.rule,
+        )?;
+    }
 
-        for t in self.0.iter() {
-            write!(
+    Ok(())
+}
+
+/// 読み込んだままのタスクを1行ずつ出力する
+pub fn write_tasks_to<W: Write>(w: &mut W, v: &[Task]) -> Result<(), Box<dyn Error>> {
+    writeln!(
+        w,
+        "|行|タスクID|日付|タスク|プロジェクト|予定|開始時刻|終了時刻|実績|休日|コメント|\n|---|---|---|---|---|---|---|---|---|---|---|"
+    )?;
+    let time = |t: Option<NaiveDateTime>| {
+        t.map(|t| t.format("%H:%M").to_string())
+            .unwrap_or_else(|| "-".into())
+    };
+    for t in v {
+        writeln!(
+            w,
+            "|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|",
+            t.row,
+            t.id,
+            t.date.format("%Y-%m-%d"),
+            t.name,
+            t.project
+                .as_ref()
+                .map(|p| format!("{} - {}", p.id, p.name))
+                .unwrap_or_else(|| "-".into()),
+            t.estimated_time
+                .map(|e| e.num_minutes().to_string())
+                .unwrap_or_else(|| "-".into()),
+            time(t.begin_time),
+            time(t.end_time),
+            t.begin_time
+                .and_then(|b| t.end_time.map(|e| (e - b).num_minutes().to_string()))
+                .unwrap_or_else(|| "-".into()),
+            match (t.holiday, &t.holiday_name) {
+                (_, Some(n)) => n.as_str(),
+                (true, None) => "休日",
+                (false, None) => "",
+            },
+            t.comment.clone().unwrap_or_default()
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn write_pivot_to<W: Write>(w: &mut W, v: &PivotResult) -> Result<(), Box<dyn Error>> {
+    writeln!(
+        w,
+        "# {} の{}（{}×{}）\n",
+        v.project_name,
+        v.metric.label(),
+        v.rows.label(),
+        v.cols.label()
+    )?;
+    writeln!(
+        w,
+        "|{}＼{}|{}|合計|\n|---|{}---|",
+        v.rows.label(),
+        v.cols.label(),
+        v.col_keys.join("|"),
+        "---|".repeat(v.col_keys.len())
+    )?;
+    for ((k, cells), total) in v
+        .row_keys
+        .iter()
+        .zip(v.cells.iter())
+        .zip(v.row_totals.iter())
+    {
+        writeln!(
+            w,
+            "|{}|{}|{}|",
+            k,
+            cells.iter().map(|c| v.metric.format(*c)).join("|"),
+            v.metric.format(*total)
+        )?;
+    }
+    writeln!(
+        w,
+        "|合計|{}|{}|",
+        v.col_totals.iter().map(|c| v.metric.format(*c)).join("|"),
+        v.metric.format(v.total)
+    )?;
+
+    Ok(())
+}
+
+struct DiffTable<'a>(&'a TasksDiff);
+
+impl<'a> Display for DiffTable<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn int(f: &mut fmt::Formatter, label: &str, d: &Delta<i64>) -> fmt::Result {
+            row(f, label, d, |v| v.to_string(), |v| format!("{:+}", v))
+        }
+
+        fn float(f: &mut fmt::Formatter, label: &str, d: &Delta<f64>) -> fmt::Result {
+            row(
                 f,
-                "|{name}|{date}|{begin}|{end}|{estimated}|{timespan}|{gap}|{comment}|\n",
-                name = t.name,
-                date = t.begin_time.date().format("%Y-%m-%d"),
-                begin = t.begin_time.time().format("%H:%M"),
-                end = t.end_time.time().format("%H:%M"),
-                estimated = t
-                    .estimated_time
-                    .map(|e| e.to_string())
-                    .unwrap_or("-".to_string()),
-                timespan = t.timespan,
-                gap = t
-                    .time_gap_ratio
-                    .map(|r| format!("{:.2}", r))
-                    .unwrap_or("-".to_string()),
-                comment = t.comment.clone().unwrap_or(String::new())
-            )?;
+                label,
+                d,
+                |v| format!("{:.2}", v),
+                |v| format!("{:+.2}", v),
+            )
         }
 
-        Ok(())
+        fn time<T: Copy + Into<Timespan>>(
+            f: &mut fmt::Formatter,
+            label: &str,
+            d: &Delta<T>,
+        ) -> fmt::Result {
+            row(f, label, d, |v| v.into().to_string(), |v| v.into().signed())
+        }
+
+        fn row<T: Copy>(
+            f: &mut fmt::Formatter,
+            label: &str,
+            d: &Delta<T>,
+            value: impl Fn(T) -> String,
+            delta: impl Fn(T) -> String,
+        ) -> fmt::Result {
+            writeln!(
+                f,
+                "|{}|{}|{}|{}|",
+                label,
+                d.before.map(&value).unwrap_or("-".to_string()),
+                d.after.map(&value).unwrap_or("-".to_string()),
+                d.delta.map(&delta).unwrap_or("-".to_string()),
+            )
+        }
+
+        let d = self.0;
+        writeln!(f, "|項目|前|後|差分|\n|---|---|---|---|")?;
+        time(f, "合計見積時間", &d.total_estimated_time)?;
+        time(f, "合計所要時間", &d.total_work_time)?;
+        float(f, "実績/予定", &d.total_time_gap_ratio)?;
+        int(f, "稼働日数", &d.work_days)?;
+        time(f, "1日あたり所要時間 平均", &d.work_time_per_day)?;
+        time(f, "1日あたり所要時間 最大", &d.work_time_per_day_max)?;
+        time(f, "1日あたり所要時間 最小", &d.work_time_per_day_min)?;
+        time(f, "1日あたり所要時間 中央", &d.work_time_per_day_median)?;
+        time(
+            f,
+            "1日あたり所要時間 標準偏差",
+            &d.work_time_per_day_deviation,
+        )?;
+        time(f, "1ページあたりの所要時間", &d.work_time_per_value)?;
+        int(f, "セッション数", &d.sessions)?;
+        time(f, "セッションあたり時間 平均", &d.session_time_avg)?;
+        time(f, "セッションあたり時間 最長", &d.session_time_max)?;
+        time(f, "空き時間", &d.idle_time)?;
+        int(f, "工程の切り替え回数", &d.group_switches)?;
+        int(f, "プロジェクトの切り替え回数", &d.project_switches)
     }
 }
 
-struct Group<'a>(&'a Vec<(String, TasksAnalysisResult)>, Option<i64>);
+struct DiffTaskList<'a>(&'a [DiffTask]);
 
-impl<'a> Display for Group<'a> {
+impl<'a> Display for DiffTaskList<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for (k, v) in self.0 {
-            write!(
+        if self.0.is_empty() {
+            return writeln!(f, "なし");
+        }
+
+        for t in self.0 {
+            writeln!(
                 f,
-                "### {}\n\n{}\n\n{}\n",
-                k,
-                Analysis(v, self.1),
-                TaskTable(&v.tasks)
+                "- {} ({}){}",
+                t.name,
+                t.id,
+                if t.done { "" } else { " （未完了）" }
             )?;
         }
         Ok(())
     }
 }
 
-struct Analysis<'a>(&'a TasksAnalysisResult, Option<i64>);
+pub fn write_compare_to<W: Write>(w: &mut W, v: &CompareResult) -> Result<(), Box<dyn Error>> {
+    let names: Vec<_> = v.projects.iter().map(|p| p.project_name.as_str()).collect();
+    let all: Vec<_> = v.projects.iter().map(|p| Some(p.all.clone())).collect();
+
+    write!(
+        w,
+        r#"# プロジェクト比較
+
+## 全タスク
+
+{all}
+## 工程別
+
+{group}"#,
+        all = CompareTable(&names, &all),
+        group = v
+            .group
+            .iter()
+            .map(|(k, m)| format!("### {}\n\n{}\n", k, CompareTable(&names, m)))
+            .join(""),
+    )?;
+
+    Ok(())
+}
 
-impl<'a> Display for Analysis<'a> {
+struct CompareTable<'a>(&'a [&'a str], &'a [Option<CompareMetrics>]);
+
+impl<'a> Display for CompareTable<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let estimated_time = Timespan::from(self.0.total_estimated_time);
-        let work_time = Timespan::from(self.0.total_work_time);
-        let (per_day, min, max, median, deviation, per_value) = (
-            Timespan::from(self.0.work_time_per_day),
-            Timespan::from(self.0.work_time_per_day_min),
-            Timespan::from(self.0.work_time_per_day_max),
-            Timespan::from(self.0.work_time_per_day_median),
-            Timespan::from(self.0.work_time_per_day_deviation),
-            self.0.work_time_per_value.map(Timespan::from),
-        );
+        let row = |f: &mut fmt::Formatter,
+                   label: &str,
+                   value: &dyn Fn(&CompareMetrics) -> Option<String>|
+         -> fmt::Result {
+            writeln!(
+                f,
+                "|{}|{}|",
+                label,
+                self.1
+                    .iter()
+                    .map(|m| m.as_ref().and_then(value).unwrap_or("-".to_string()))
+                    .join("|")
+            )
+        };
 
-        write!(
+        writeln!(
             f,
-            r#"- 合計見積時間: {estimated_time}
-- 合計所要時間: {work_time}{gap}
-- 稼働日数： {period}d
-- 1日あたり所要時間
-    - 平均：{per_day}
-    - 最大：{max}
-    - 最小：{min}
-    - 中央：{median}
-    - 標準偏差：{deviation}{per_value}"#,
-            estimated_time = estimated_time,
-            work_time = work_time,
-            gap = self
-                .0
-                .total_time_gap_ratio
-                .map(|r| format!(" (x{:.2})", r))
-                .unwrap_or_default(),
-            period = self.0.work_days,
-            per_day = per_day,
-            min = min,
-            max = max,
-            median = median,
-            deviation = deviation,
-            per_value = per_value
-                .and_then(|v| self.1.map(|w| (v, w)))
-                .map(|(v, w)| format!("\n- 1ページあたりの所要時間： {} （全{}ページ）", v, w))
-                .unwrap_or_default(),
-        )
+            "|項目|{}|\n|---|{}|",
+            self.0.join("|"),
+            self.0.iter().map(|_| "---").join("|")
+        )?;
+        row(f, "合計見積時間", &|m| {
+            Some(Timespan::from(m.total_estimated_time).to_string())
+        })?;
+        row(f, "合計所要時間", &|m| {
+            Some(Timespan::from(m.total_work_time).to_string())
+        })?;
+        row(f, "実績/予定", &|m| {
+            m.total_time_gap_ratio.map(|r| format!("{:.2}", r))
+        })?;
+        row(f, "稼働日数", &|m| Some(m.work_days.to_string()))?;
+        row(f, "1日あたり所要時間 平均", &|m| {
+            Some(Timespan::from(m.work_time_per_day).to_string())
+        })?;
+        row(f, "1ページあたりの所要時間", &|m| {
+            m.work_time_per_value.map(|v| Timespan::from(v).to_string())
+        })
     }
 }
 
@@ -169,6 +600,19 @@ impl From<f64> for Timespan {
     }
 }
 
+impl Timespan {
+    /// 符号をつけて表示する（差分用）
+    fn signed(&self) -> String {
+        if self.0 < Duration::zero() {
+            format!("-{}", Timespan(-self.0))
+        } else if self.0.is_zero() {
+            "+0".to_string()
+        } else {
+            format!("+{}", self)
+        }
+    }
+}
+
 impl Display for Timespan {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if self.0.is_zero() {
@@ -230,6 +674,171 @@ impl Display for Timespan {
 }
 
 pub fn ceil(value: f64, scale: i8) -> f64 {
-    let multiplier = 10f64.powi(scale as i32) as f64;
+    let multiplier = 10f64.powi(scale as i32);
     (value * multiplier).ceil() / multiplier
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        holiday::HolidayCalendar,
+        test_util::{analyze, at, task},
+        Query, SortKey, TaskOrder,
+    };
+
+    #[test]
+    fn report_of_unfinished_tasks_only() {
+        let task = task("1", "執筆")
+            .project("300", "本")
+            .estimated(60)
+            .started(at(1, 10, 0))
+            .build();
+        let res = analyze(vec![task], "300", Some(300)).unwrap();
+        assert!(res.all.work_time_per_day.is_nan());
+
+        let mut out = vec![];
+        write_to(&mut out, &res).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("    - 平均：-\n"));
+        assert!(out.contains("## 残タスク"));
+    }
+
+    #[test]
+    fn remaining_table_follows_task_table_options() {
+        let tasks = (1..=3)
+            .map(|i| {
+                task(&i.to_string(), &format!("執筆 {}", i))
+                    .project("300", "本")
+                    .estimated(60)
+                    .build()
+            })
+            .collect();
+        let res = analyze(tasks, "300", None).unwrap();
+        let render = |options: ReportOptions| {
+            let mut out = vec![];
+            write_report_to(
+                &mut out,
+                &res,
+                &ReportOptions {
+                    sections: Some(vec!["remaining".to_string()]),
+                    ..options
+                },
+            )
+            .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let out = render(ReportOptions {
+            max_tasks: Some(1),
+            ..ReportOptions::default()
+        });
+        assert!(out.contains("|執筆 1|60|"));
+        assert!(!out.contains("|執筆 2|"));
+        assert!(out.contains("|（ほか2件）|||||"));
+
+        let out = render(ReportOptions {
+            task_table: false,
+            ..ReportOptions::default()
+        });
+        assert!(out.contains("- 残タスク数： 3"));
+        assert!(!out.contains("|タスク|"));
+    }
+
+    #[test]
+    fn top_keeps_the_first_tasks_after_sorting() {
+        let tasks = [30, 90, 60]
+            .iter()
+            .enumerate()
+            .map(|(i, m)| {
+                task(&i.to_string(), &format!("執筆 {}", i))
+                    .project("300", "本")
+                    .time(
+                        at(1, 9 + i as u32 * 2, 0),
+                        at(1, 9 + i as u32 * 2, 0) + Duration::minutes(*m),
+                    )
+                    .build()
+            })
+            .collect();
+        let calendar = HolidayCalendar::default();
+        let res = Query::new()
+            .project("300")
+            .task_order(TaskOrder {
+                key: SortKey::Timespan,
+                descending: true,
+            })
+            .calendar(&calendar)
+            .analyze(tasks)
+            .unwrap();
+
+        let mut out = vec![];
+        write_report_to(
+            &mut out,
+            &res,
+            &ReportOptions {
+                sections: Some(vec!["tasks".to_string()]),
+                max_tasks: Some(2),
+                top: Some(1),
+                ..ReportOptions::default()
+            },
+        )
+        .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("|執筆 1|"));
+        assert!(!out.contains("|執筆 0|"));
+        assert!(!out.contains("|執筆 2|"));
+        assert!(out.contains("|（ほか2件）|"));
+    }
+
+    #[test]
+    fn diff_times_are_signed_timespans() {
+        let result = |minutes: i64| {
+            let tasks = vec![task("1", "執筆 1章")
+                .project("300", "本")
+                .time(at(1, 9, 0), at(1, 9, 0) + Duration::minutes(minutes))
+                .build()];
+            analyze(tasks, "300", None).unwrap()
+        };
+        let table = |before: i64, after: i64| {
+            DiffTable(&crate::diff::diff(&result(before), &result(after)).all).to_string()
+        };
+
+        assert!(table(45, 30).contains("|合計所要時間|45m|30m|-15m|\n"));
+        assert!(table(30, 45).contains("|合計所要時間|30m|45m|+15m|\n"));
+        assert!(table(30, 30).contains("|合計所要時間|30m|30m|+0|\n"));
+        assert!(table(30, 45).contains("|稼働日数|1|1|+0|\n"));
+    }
+
+    #[test]
+    fn routine_times_are_timespans() {
+        let tasks = vec![
+            task("1", "日報 6/1")
+                .project("300", "本")
+                .time(at(1, 18, 0), at(1, 18, 20))
+                .build(),
+            task("2", "日報 6/2")
+                .project("300", "本")
+                .time(at(2, 18, 0), at(2, 18, 30))
+                .build(),
+        ];
+        let calendar = HolidayCalendar::default();
+        let res = Query::new()
+            .project("300")
+            .routines(true)
+            .calendar(&calendar)
+            .analyze(tasks)
+            .unwrap();
+        let mut out = vec![];
+        write_report_to(
+            &mut out,
+            &res,
+            &ReportOptions {
+                sections: Some(vec!["routines".to_string()]),
+                ..ReportOptions::default()
+            },
+        )
+        .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("|日報|2|50m|25m|30m|+10.0|2020-06-01〜2020-06-02|"));
+    }
+}
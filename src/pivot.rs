@@ -0,0 +1,279 @@
+use crate::{grouping::GroupKey, AnalysisResultTask};
+use serde::{Serialize, Serializer};
+use std::{
+    collections::BTreeSet,
+    error::Error,
+    fmt::{self, Display},
+    io::Write,
+    str::FromStr,
+};
+
+/// ピボットの各セルに集計する値
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    /// 作業時間（分）
+    WorkTime,
+    /// 見積時間（分）
+    EstimatedTime,
+    /// タスク数
+    Count,
+    /// 実績/予定
+    GapRatio,
+}
+
+impl Metric {
+    /// レポートの見出しに使う名前
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::WorkTime => "所要時間",
+            Self::EstimatedTime => "見積時間",
+            Self::Count => "タスク数",
+            Self::GapRatio => "実績/予定",
+        }
+    }
+
+    fn value(&self, tasks: &[&AnalysisResultTask]) -> Option<f64> {
+        let work_time: i64 = tasks.iter().map(|t| t.timespan).sum();
+        let estimated_time: i64 = tasks.iter().filter_map(|t| t.estimated_time).sum();
+        match self {
+            Self::WorkTime => Some(work_time as f64),
+            Self::EstimatedTime => Some(estimated_time as f64),
+            Self::Count => Some(tasks.len() as f64),
+            Self::GapRatio => {
+                if estimated_time == 0 {
+                    None
+                } else {
+                    Some(work_time as f64 / estimated_time as f64)
+                }
+            }
+        }
+    }
+
+    /// 比率は小数2桁、それ以外は整数で表示する
+    pub fn format(&self, v: Option<f64>) -> String {
+        match (self, v) {
+            (_, None) => "-".into(),
+            (Self::GapRatio, Some(v)) => format!("{:.2}", v),
+            (_, Some(v)) => format!("{}", v as i64),
+        }
+    }
+}
+
+impl Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::WorkTime => "work_time",
+                Self::EstimatedTime => "estimated_time",
+                Self::Count => "count",
+                Self::GapRatio => "gap_ratio",
+            }
+        )
+    }
+}
+
+impl FromStr for Metric {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "work_time" => Ok(Self::WorkTime),
+            "estimated_time" => Ok(Self::EstimatedTime),
+            "count" => Ok(Self::Count),
+            "gap_ratio" => Ok(Self::GapRatio),
+            _ => Err("invalid metric"),
+        }
+    }
+}
+
+impl Serialize for Metric {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(self)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PivotResult {
+    pub project_name: String,
+    pub rows: GroupKey,
+    pub cols: GroupKey,
+    pub metric: Metric,
+    pub row_keys: Vec<String>,
+    pub col_keys: Vec<String>,
+    /// 行・列の順に並べたセルの値（該当するタスクがなければなし）
+    pub cells: Vec<Vec<Option<f64>>>,
+    /// 行ごとの合計
+    pub row_totals: Vec<Option<f64>>,
+    /// 列ごとの合計
+    pub col_totals: Vec<Option<f64>>,
+    pub total: Option<f64>,
+}
+
+/// 分類の（並び順、キー）
+type Key = (i64, String);
+
+/// 完了済みタスクを行・列の観点で分類し、セルごとに値を集計する
+///
+/// タグのように1つのタスクが複数の分類に入る観点では、合計はタスクを重複して数えない。
+pub(crate) fn pivot(
+    tasks: &[AnalysisResultTask],
+    project_name: String,
+    rows: &GroupKey,
+    cols: &GroupKey,
+    metric: Metric,
+) -> PivotResult {
+    let keyed: Vec<_> = tasks
+        .iter()
+        .map(|t| (rows.keys(t), cols.keys(t), t))
+        .collect();
+    let row_keys: Vec<_> = keyed
+        .iter()
+        .flat_map(|(r, _, _)| r.iter().cloned())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|(_, k)| k)
+        .collect();
+    let col_keys: Vec<_> = keyed
+        .iter()
+        .flat_map(|(_, c, _)| c.iter().cloned())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|(_, k)| k)
+        .collect();
+    let has = |keys: &[Key], k: &str| keys.iter().any(|(_, k2)| k2 == k);
+    let value = |f: &dyn Fn(&[Key], &[Key]) -> bool| {
+        let tasks: Vec<_> = keyed
+            .iter()
+            .filter(|(r, c, _)| f(r, c))
+            .map(|(_, _, t)| *t)
+            .collect();
+        if tasks.is_empty() {
+            None
+        } else {
+            metric.value(&tasks)
+        }
+    };
+
+    PivotResult {
+        cells: row_keys
+            .iter()
+            .map(|rk| {
+                col_keys
+                    .iter()
+                    .map(|ck| value(&|r, c| has(r, rk) && has(c, ck)))
+                    .collect()
+            })
+            .collect(),
+        row_totals: row_keys
+            .iter()
+            .map(|rk| value(&|r, _| has(r, rk)))
+            .collect(),
+        col_totals: col_keys
+            .iter()
+            .map(|ck| value(&|_, c| has(c, ck)))
+            .collect(),
+        total: value(&|_, _| true),
+        project_name,
+        rows: rows.clone(),
+        cols: cols.clone(),
+        metric,
+        row_keys,
+        col_keys,
+    }
+}
+
+/// 1行目を列のキー、1列目を行のキーとしたCSVで出力する（最後の行・列は合計、値のないセルは空）
+pub fn write_csv_to<W: Write>(w: &mut W, v: &PivotResult) -> Result<(), Box<dyn Error>> {
+    let cell = |c: &Option<f64>| c.map(|_| v.metric.format(*c)).unwrap_or_default();
+    let mut csv = csv::Writer::from_writer(w);
+    csv.write_record(
+        std::iter::once(format!("{}＼{}", v.rows.label(), v.cols.label()))
+            .chain(v.col_keys.iter().cloned())
+            .chain(std::iter::once("合計".to_string())),
+    )?;
+    for ((k, cells), total) in v
+        .row_keys
+        .iter()
+        .zip(v.cells.iter())
+        .zip(v.row_totals.iter())
+    {
+        csv.write_record(
+            std::iter::once(k.clone())
+                .chain(cells.iter().map(cell))
+                .chain(std::iter::once(cell(total))),
+        )?;
+    }
+    csv.write_record(
+        std::iter::once("合計".to_string())
+            .chain(v.col_totals.iter().map(cell))
+            .chain(std::iter::once(cell(&v.total))),
+    )?;
+    csv.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{at, task};
+
+    #[test]
+    fn totals_count_tasks_in_several_rows_once() {
+        let tasks = [
+            task("1", "執筆 #本 #急ぎ")
+                .time(at(1, 9, 0), at(1, 10, 0))
+                .done(),
+            task("2", "校正 #本")
+                .time(at(1, 10, 0), at(1, 10, 30))
+                .done(),
+            task("3", "メール").time(at(1, 11, 0), at(1, 11, 15)).done(),
+        ];
+        let res = pivot(
+            &tasks,
+            "本".to_string(),
+            &GroupKey::Tag,
+            &GroupKey::Group,
+            Metric::WorkTime,
+        );
+
+        assert_eq!(res.row_keys, vec!["-", "急ぎ", "本"]);
+        assert_eq!(res.col_keys, vec!["-", "執筆", "校正"]);
+        assert_eq!(
+            res.cells,
+            vec![
+                vec![Some(15.0), None, None],
+                vec![None, Some(60.0), None],
+                vec![None, Some(60.0), Some(30.0)],
+            ]
+        );
+        assert_eq!(res.row_totals, vec![Some(15.0), Some(60.0), Some(90.0)]);
+        assert_eq!(res.col_totals, vec![Some(15.0), Some(60.0), Some(30.0)]);
+        assert_eq!(res.total, Some(105.0));
+    }
+
+    #[test]
+    fn gap_ratio_needs_an_estimate() {
+        let tasks = [
+            task("1", "執筆 1章")
+                .estimated(40)
+                .time(at(1, 9, 0), at(1, 10, 0))
+                .done(),
+            task("2", "校正 1章")
+                .time(at(1, 10, 0), at(1, 10, 30))
+                .done(),
+        ];
+        let res = pivot(
+            &tasks,
+            "本".to_string(),
+            &GroupKey::Group,
+            &GroupKey::Month,
+            Metric::GapRatio,
+        );
+        assert_eq!(res.cells, vec![vec![Some(1.5)], vec![None]]);
+        assert_eq!(res.total, Some(90.0 / 40.0));
+        assert_eq!(Metric::GapRatio.format(res.total), "2.25");
+        assert_eq!(Metric::GapRatio.format(None), "-");
+    }
+}
@@ -0,0 +1,211 @@
+use crate::Task;
+use chrono::NaiveDate;
+use itertools::Itertools;
+use serde::Serialize;
+use std::{cmp::Ordering, str::FromStr};
+
+/// プロジェクトのないタスクをまとめた項目の名前
+pub const NO_PROJECT: &str = "(none)";
+
+/// プロジェクトのないタスクを1つのプロジェクトとして扱うときのID（`tcc analyze --project -`）
+pub const UNASSIGNED_ID: &str = "-";
+
+/// プロジェクトごとのタスクの集計
+#[derive(Debug, Serialize)]
+pub struct ProjectSummary {
+    /// プロジェクトID（プロジェクトのないタスクではなし）
+    pub id: Option<String>,
+    pub name: String,
+    /// 終わっていないタスクを含むタスク数
+    pub tasks: i64,
+    /// 完了済みタスクの所要時間（分）
+    pub work_time: i64,
+    /// 全プロジェクトの所要時間に占める割合
+    pub share: Option<f64>,
+    /// 完了済みタスクを最初に行った日
+    pub first_date: Option<NaiveDate>,
+    /// 完了済みタスクを最後に行った日
+    pub last_date: Option<NaiveDate>,
+}
+
+/// プロジェクトの一覧の並び順
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectSortKey {
+    Id,
+    Name,
+    Tasks,
+    WorkTime,
+    First,
+    Last,
+}
+
+impl FromStr for ProjectSortKey {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "id" => Ok(Self::Id),
+            "name" => Ok(Self::Name),
+            "tasks" => Ok(Self::Tasks),
+            "work-time" => Ok(Self::WorkTime),
+            "first" => Ok(Self::First),
+            "last" => Ok(Self::Last),
+            _ => Err("invalid sort key"),
+        }
+    }
+}
+
+impl ProjectSortKey {
+    /// 数字のIDは数値として比べ、値のないもの（プロジェクトのないタスク、完了済みタスクのないプロジェクト）は最後にする
+    fn cmp(&self, a: &ProjectSummary, b: &ProjectSummary, descending: bool) -> Ordering {
+        fn some_first<T: Ord>(a: Option<T>, b: Option<T>, descending: bool) -> Ordering {
+            match (a, b) {
+                (Some(a), Some(b)) if descending => b.cmp(&a),
+                (Some(a), Some(b)) => a.cmp(&b),
+                (a, b) => b.is_some().cmp(&a.is_some()),
+            }
+        }
+        let id = |p: &ProjectSummary| p.id.as_ref().map(|id| (id.parse::<i64>().ok(), id.clone()));
+        let order = match self {
+            Self::Id => some_first(id(a), id(b), descending),
+            Self::Name => some_first(Some(&a.name), Some(&b.name), descending),
+            Self::Tasks => some_first(Some(a.tasks), Some(b.tasks), descending),
+            Self::WorkTime => some_first(Some(a.work_time), Some(b.work_time), descending),
+            Self::First => some_first(a.first_date, b.first_date, descending),
+            Self::Last => some_first(a.last_date, b.last_date, descending),
+        };
+        order.then_with(|| some_first(id(a), id(b), false))
+    }
+}
+
+/// タスクをプロジェクトごとに集計する（`include_none` ならプロジェクトのないタスクも [`NO_PROJECT`] としてまとめる）
+pub fn projects(
+    tasks: &[Task],
+    include_none: bool,
+    sort: ProjectSortKey,
+    descending: bool,
+) -> Vec<ProjectSummary> {
+    let work_time = |t: &Task| {
+        t.begin_time
+            .and_then(|b| t.end_time.map(|e| (e - b).num_minutes()))
+    };
+    let total: i64 = tasks.iter().filter_map(work_time).sum();
+
+    tasks
+        .iter()
+        .filter(|t| include_none || t.project.is_some())
+        .map(|t| (t.project.clone(), t))
+        .into_group_map()
+        .into_iter()
+        .map(|(project, tasks)| {
+            let done: Vec<_> = tasks
+                .iter()
+                .filter_map(|t| work_time(t).map(|w| (t.begin_time.unwrap().date(), w)))
+                .collect();
+            let work_time: i64 = done.iter().map(|(_, w)| w).sum();
+            ProjectSummary {
+                id: project.as_ref().map(|p| p.id.clone()),
+                name: project.map(|p| p.name).unwrap_or_else(|| NO_PROJECT.into()),
+                tasks: tasks.len() as i64,
+                work_time,
+                share: if total == 0 {
+                    None
+                } else {
+                    Some(work_time as f64 / total as f64)
+                },
+                first_date: done.iter().map(|(d, _)| *d).min(),
+                last_date: done.iter().map(|(d, _)| *d).max(),
+            }
+        })
+        .sorted_by(|a, b| sort.cmp(a, b, descending))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{at, task};
+
+    fn tasks() -> Vec<Task> {
+        vec![
+            task("1", "1")
+                .project("10", "本")
+                .time(at(2, 9, 0), at(2, 10, 0))
+                .build(),
+            task("2", "2")
+                .project("9", "雑誌")
+                .time(at(1, 9, 0), at(1, 9, 30))
+                .build(),
+            task("3", "3")
+                .project("9", "雑誌")
+                .time(at(3, 9, 0), at(3, 9, 30))
+                .build(),
+            task("4", "4").project("300", "社内").build(),
+            task("5", "5").time(at(1, 10, 0), at(1, 10, 30)).build(),
+        ]
+    }
+
+    fn names(sort: ProjectSortKey, descending: bool) -> Vec<String> {
+        projects(&tasks(), true, sort, descending)
+            .into_iter()
+            .map(|p| p.name)
+            .collect()
+    }
+
+    #[test]
+    fn numeric_ids_sort_as_numbers_and_missing_values_last() {
+        assert_eq!(
+            names(ProjectSortKey::Id, false),
+            vec!["雑誌", "本", "社内", NO_PROJECT]
+        );
+        assert_eq!(
+            names(ProjectSortKey::Id, true),
+            vec!["社内", "本", "雑誌", NO_PROJECT]
+        );
+        assert_eq!(
+            names(ProjectSortKey::First, true),
+            vec!["本", "雑誌", NO_PROJECT, "社内"]
+        );
+    }
+
+    #[test]
+    fn ties_are_broken_by_id() {
+        assert_eq!(
+            names(ProjectSortKey::WorkTime, true),
+            vec!["雑誌", "本", NO_PROJECT, "社内"]
+        );
+        assert_eq!(
+            names(ProjectSortKey::Tasks, false),
+            vec!["本", "社内", NO_PROJECT, "雑誌"]
+        );
+    }
+
+    #[test]
+    fn summaries_have_share_and_dates() {
+        let res = projects(&tasks(), false, ProjectSortKey::Id, false);
+        let summary: Vec<_> = res
+            .iter()
+            .map(|p| (p.id.as_deref(), p.tasks, p.work_time, p.share, p.last_date))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    Some("9"),
+                    2,
+                    60,
+                    Some(0.4),
+                    Some(NaiveDate::from_ymd(2020, 6, 3))
+                ),
+                (
+                    Some("10"),
+                    1,
+                    60,
+                    Some(0.4),
+                    Some(NaiveDate::from_ymd(2020, 6, 2))
+                ),
+                (Some("300"), 1, 0, Some(0.0), None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,329 @@
+use crate::{
+    analyzer::{analyze_tasks, TaskOrder, Tasks, TasksAnalysisResult, Timeline},
+    grouping::{GroupKey, Grouping},
+    holiday::{HolidayCalendar, HolidayProvider},
+    pivot::{pivot, Metric, PivotResult},
+    projects::{NO_PROJECT, UNASSIGNED_ID},
+    routine::{classify, RoutineRules},
+    working_hours::WorkingHours,
+    AnalysisResult, AnalysisResultTask, DateRange, Task,
+};
+use itertools::Itertools;
+use std::rc::Rc;
+
+/// プロジェクトの分析で、プロジェクトを指定しなかったときの名前
+const ALL_PROJECTS: &str = "全プロジェクト";
+
+/// タスクの絞り込み条件
+type Filter<'a> = Box<dyn Fn(&Task) -> bool + 'a>;
+
+/// タスクを絞り込んで分析する
+///
+/// ```no_run
+/// # use tcc::{csv_parser, Query};
+/// # let tasks = csv_parser::parse(std::fs::File::open("taskchute.tsv").unwrap());
+/// let result = Query::new()
+///     .project("100")
+///     .filter(|t| t.name.contains("執筆"))
+///     .value(Some(200))
+///     .summarize(tasks);
+/// ```
+pub struct Query<'a> {
+    pub(crate) project_id: Option<String>,
+    pub(crate) date_range: Option<DateRange>,
+    pub(crate) filters: Vec<Filter<'a>>,
+    pub(crate) value: Option<i64>,
+    pub(crate) groupings: Vec<Grouping>,
+    pub(crate) routines: bool,
+    pub(crate) routine_rules: RoutineRules,
+    pub(crate) task_order: TaskOrder,
+    pub(crate) session_gap: i64,
+    pub(crate) working_hours: WorkingHours,
+    pub(crate) calendar: Option<&'a dyn HolidayProvider>,
+}
+
+impl<'a> Default for Query<'a> {
+    fn default() -> Self {
+        Self {
+            project_id: None,
+            date_range: None,
+            filters: vec![],
+            value: None,
+            groupings: vec![],
+            routines: false,
+            routine_rules: RoutineRules::default(),
+            task_order: TaskOrder::default(),
+            session_gap: 5,
+            working_hours: WorkingHours::default(),
+            calendar: None,
+        }
+    }
+}
+
+impl<'a> Query<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// プロジェクトIDで絞り込む（[`UNASSIGNED_ID`] ならプロジェクトのないタスク）
+    pub fn project(mut self, project_id: impl Into<String>) -> Self {
+        self.project_id = Some(project_id.into());
+        self
+    }
+
+    /// 実行日で絞り込む
+    pub fn date_range(mut self, date_range: DateRange) -> Self {
+        self.date_range = Some(date_range);
+        self
+    }
+
+    /// 条件を満たすタスクに絞り込む（複数指定するとすべてを満たすタスク）
+    pub fn filter<F: Fn(&Task) -> bool + 'a>(mut self, f: F) -> Self {
+        self.filters.push(Box::new(f));
+        self
+    }
+
+    /// 1ページあたりの作業時間を求めるときのページ数などの値
+    pub fn value(mut self, value: Option<i64>) -> Self {
+        self.value = value;
+        self
+    }
+
+    /// [`analyze`](Self::analyze) で分類する観点を追加する（指定しなければ平日休日別・工程別・種類別）
+    pub fn grouping(mut self, grouping: Grouping) -> Self {
+        self.groupings.push(grouping);
+        self
+    }
+
+    /// [`analyze`](Self::analyze) で同じ名前で繰り返し行ったタスクを集計する
+    pub fn routines(mut self, routines: bool) -> Self {
+        self.routines = routines;
+        self
+    }
+
+    /// 定型タスクの判定条件（既定は3日以上に現れるタスク）
+    pub fn routine_rules(mut self, rules: RoutineRules) -> Self {
+        self.routine_rules = rules;
+        self
+    }
+
+    /// 結果のタスクの一覧の並び順（既定は開始時刻順）
+    pub fn task_order(mut self, order: TaskOrder) -> Self {
+        self.task_order = order;
+        self
+    }
+
+    /// この分数未満の間隔で続くタスクを1つのセッションとみなす（既定は5分）
+    pub fn session_gap(mut self, minutes: u32) -> Self {
+        self.session_gap = minutes.into();
+        self
+    }
+
+    /// 所定労働時間の設定
+    pub fn working_hours(mut self, working_hours: WorkingHours) -> Self {
+        self.working_hours = working_hours;
+        self
+    }
+
+    /// 休日の判定に使うカレンダー（既定は組み込みの祝日）
+    pub fn calendar(mut self, calendar: &'a dyn HolidayProvider) -> Self {
+        self.calendar = Some(calendar);
+        self
+    }
+
+    /// 条件に合うタスクを返す
+    pub fn tasks(&self, tasks: Vec<Task>) -> Vec<Task> {
+        tasks.into_iter().filter(|t| self.matches(t)).collect()
+    }
+
+    fn matches(&self, t: &Task) -> bool {
+        self.project_id
+            .as_ref()
+            .map(|id| match &t.project {
+                Some(p) => &p.id == id,
+                None => id == UNASSIGNED_ID,
+            })
+            .unwrap_or(true)
+            && self.date_range.map(|r| r.contains(&t.date)).unwrap_or(true)
+            && self.filters.iter().all(|f| f(t))
+    }
+
+    /// 条件に合う完了済みタスクを集計する
+    pub fn summarize(&self, tasks: Vec<Task>) -> TasksAnalysisResult {
+        let mut result = self.done_tasks(tasks).analyze();
+        result.order_tasks(&self.task_order);
+        result
+    }
+
+    /// 条件に合う完了済みタスクをキーごとに集計する
+    pub fn group_by<F: Fn(&AnalysisResultTask) -> String>(
+        &self,
+        tasks: Vec<Task>,
+        key: F,
+    ) -> Vec<(String, TasksAnalysisResult)> {
+        self.done_tasks(tasks)
+            .group_by(|t| key(t))
+            .into_iter()
+            .map(|(k, v)| {
+                let mut result = v.analyze();
+                result.order_tasks(&self.task_order);
+                (k, result)
+            })
+            .collect()
+    }
+
+    /// 条件に合うタスクをすべての観点で分析する（プロジェクトを指定していて見つからなければなし）
+    pub fn analyze(&self, tasks: Vec<Task>) -> Option<AnalysisResult> {
+        let timeline = Timeline::new(&tasks);
+        let tasks = self.tasks(tasks);
+        let project_name = self.project_name(&tasks)?;
+        let (done, remaining): (Vec<_>, Vec<_>) = tasks
+            .into_iter()
+            .partition(|t| t.begin_time.and(t.end_time).is_some());
+
+        let builtin;
+        let calendar = match self.calendar {
+            Some(c) => c,
+            None => {
+                builtin = HolidayCalendar::builtin();
+                &builtin
+            }
+        };
+
+        Some(analyze_tasks(
+            done,
+            remaining,
+            timeline,
+            project_name,
+            self.project_id.as_deref(),
+            self,
+            calendar,
+        ))
+    }
+
+    /// 条件に合う完了済みタスクを行・列の観点で集計する（プロジェクトを指定していて見つからなければなし）
+    pub fn pivot(
+        &self,
+        tasks: Vec<Task>,
+        rows: &GroupKey,
+        cols: &GroupKey,
+        metric: Metric,
+    ) -> Option<PivotResult> {
+        let project_name = self.project_name(&self.tasks(tasks.clone()))?;
+        Some(pivot(
+            &self.done_tasks(tasks).0,
+            project_name,
+            rows,
+            cols,
+            metric,
+        ))
+    }
+
+    fn project_name(&self, tasks: &[Task]) -> Option<String> {
+        match &self.project_id {
+            Some(id) if id == UNASSIGNED_ID => Some(NO_PROJECT.to_string()),
+            Some(id) => tasks
+                .iter()
+                .filter_map(|t| t.project.as_ref())
+                .find(|p| &p.id == id)
+                .map(|p| p.name.clone()),
+            None => Some(ALL_PROJECTS.to_string()),
+        }
+    }
+
+    fn done_tasks(&self, tasks: Vec<Task>) -> Tasks {
+        let timeline = Timeline::new(&tasks);
+        let mut tasks: Vec<_> = self
+            .tasks(tasks)
+            .into_iter()
+            .filter(|t| t.begin_time.and(t.end_time).is_some())
+            .map(From::from)
+            .sorted()
+            .collect();
+        classify(&mut tasks, &self.routine_rules);
+        Tasks(tasks, self.value, self.session_gap, Rc::new(timeline))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{at, task};
+
+    fn tasks() -> Vec<Task> {
+        vec![
+            task("1", "執筆 1章")
+                .project("a", "本")
+                .time(at(1, 9, 0), at(1, 10, 0))
+                .build(),
+            task("2", "校正 1章")
+                .project("a", "本")
+                .time(at(2, 9, 0), at(2, 9, 30))
+                .build(),
+            task("3", "執筆 2章")
+                .project("a", "本")
+                .time(at(3, 9, 0), at(3, 9, 45))
+                .build(),
+            task("4", "メール")
+                .time(at(3, 10, 0), at(3, 10, 10))
+                .build(),
+            task("5", "執筆 3章")
+                .project("a", "本")
+                .estimated(60)
+                .build(),
+        ]
+    }
+
+    #[test]
+    fn filters_are_combined() {
+        let query = Query::new()
+            .project("a")
+            .date_range("2020-06-02..".parse().unwrap())
+            .filter(|t| t.name.starts_with("執筆"))
+            .filter(|t| t.begin_time.is_some());
+        let ids: Vec<_> = query.tasks(tasks()).into_iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec!["3"]);
+
+        let ids: Vec<_> = Query::new()
+            .project(UNASSIGNED_ID)
+            .tasks(tasks())
+            .into_iter()
+            .map(|t| t.id)
+            .collect();
+        assert_eq!(ids, vec!["4"]);
+    }
+
+    #[test]
+    fn summaries_use_done_tasks_and_value() {
+        let res = Query::new().project("a").value(Some(35)).summarize(tasks());
+        assert_eq!(res.total_work_time, 135);
+        assert_eq!(res.work_days, 3);
+        assert_eq!(res.work_time_per_value, Some(135.0 / 35.0));
+
+        let groups: Vec<_> = Query::new()
+            .project("a")
+            .group_by(tasks(), |t| t.group.clone().unwrap_or_default())
+            .into_iter()
+            .map(|(k, r)| (k, r.total_work_time))
+            .collect();
+        assert_eq!(
+            groups,
+            vec![("執筆".to_string(), 105), ("校正".to_string(), 30)]
+        );
+    }
+
+    #[test]
+    fn analysis_needs_a_known_project() {
+        let calendar = HolidayCalendar::default();
+        let query = Query::new().calendar(&calendar);
+        let res = query.analyze(tasks()).unwrap();
+        assert_eq!(res.project_name, ALL_PROJECTS);
+        assert_eq!(res.all.total_work_time, 145);
+        assert_eq!(res.remaining.tasks.len(), 1);
+
+        let query = Query::new().project(UNASSIGNED_ID).calendar(&calendar);
+        assert_eq!(query.analyze(tasks()).unwrap().project_name, NO_PROJECT);
+        let query = Query::new().project("z").calendar(&calendar);
+        assert!(query.analyze(tasks()).is_none());
+    }
+}
@@ -0,0 +1,287 @@
+use crate::{DateRange, Project, Task};
+use encoding_rs_io::DecodeReaderBytes;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{error::Error, fs::File, io::Read, path::Path};
+
+#[derive(Debug, Deserialize)]
+struct RuleRecord {
+    name: Option<String>,
+    comment: Option<String>,
+    period: Option<String>,
+    project_id: String,
+    project_name: Option<String>,
+    #[serde(rename = "override")]
+    overwrite: Option<String>,
+}
+
+/// タスクのプロジェクトを付け替える規則（指定した条件をすべて満たすタスクに適用する）
+#[derive(Debug)]
+pub struct RemapRule {
+    /// 規則ファイルの行番号
+    pub line: u64,
+    /// タスク名に一致する正規表現
+    pub name: Option<Regex>,
+    /// コメントに一致する正規表現
+    pub comment: Option<Regex>,
+    /// 実行日の範囲
+    pub period: Option<DateRange>,
+    pub project_id: String,
+    /// 省略したときは同じIDのプロジェクトのあるタスクから名前を探す
+    pub project_name: Option<String>,
+    /// プロジェクトのあるタスクも付け替えるか（既定はプロジェクトのないタスクだけ）
+    pub overwrite: bool,
+}
+
+impl RemapRule {
+    fn matches(&self, t: &Task) -> bool {
+        (self.overwrite || t.project.is_none())
+            && self
+                .name
+                .as_ref()
+                .map(|r| r.is_match(&t.name))
+                .unwrap_or(true)
+            && self
+                .comment
+                .as_ref()
+                .map(|r| t.comment.as_ref().map(|c| r.is_match(c)).unwrap_or(false))
+                .unwrap_or(true)
+            && self.period.map(|p| p.contains(&t.date)).unwrap_or(true)
+    }
+}
+
+/// プロジェクトを付け替えたタスク
+#[derive(Debug, Serialize)]
+pub struct Reassignment {
+    /// CSVの行番号
+    pub row: u64,
+    pub task_id: String,
+    pub task_name: String,
+    pub from: Option<Project>,
+    pub to: Project,
+    /// 適用した規則の行番号
+    pub rule: u64,
+}
+
+/// 読み込んだタスクのプロジェクトを規則に従って付け替える
+#[derive(Debug, Default)]
+pub struct Remapping {
+    pub rules: Vec<RemapRule>,
+}
+
+impl Remapping {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Self::from_csv(File::open(path)?)
+    }
+
+    /// `name,comment,period,project_id,project_name,override` 列のCSVを読み込む
+    ///
+    /// `name`・`comment` は正規表現、`period` は `2020-05-01..2020-05-31` 形式の範囲で、空の列は条件にしない。
+    /// `override` が `true` の行はプロジェクトのあるタスクも付け替える。
+    pub fn from_csv(r: impl Read) -> Result<Self, Box<dyn Error>> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(true)
+            .flexible(true)
+            .from_reader(DecodeReaderBytes::new(r));
+        let headers = reader.headers()?.clone();
+        let mut rules = vec![];
+        for record in reader.records() {
+            let record = record?;
+            let line = record.position().map(|p| p.line()).unwrap_or_default();
+            let record: RuleRecord = record
+                .deserialize(Some(&headers))
+                .map_err(|e| format!("line {}: {}", line, e))?;
+            let project_id = record.project_id.trim().to_string();
+            if project_id.is_empty() {
+                return Err(format!("line {}: empty project_id", line).into());
+            }
+            let non_empty = |s: Option<String>| s.filter(|s| !s.trim().is_empty());
+            let regex = |s: Option<String>| -> Result<Option<Regex>, Box<dyn Error>> {
+                Ok(non_empty(s)
+                    .map(|s| Regex::new(&s))
+                    .transpose()
+                    .map_err(|e| format!("line {}: {}", line, e))?)
+            };
+            rules.push(RemapRule {
+                line,
+                name: regex(record.name)?,
+                comment: regex(record.comment)?,
+                period: non_empty(record.period)
+                    .map(|p| p.trim().parse())
+                    .transpose()
+                    .map_err(|e| format!("line {}: {}", line, e))?,
+                project_id,
+                project_name: non_empty(record.project_name),
+                overwrite: match non_empty(record.overwrite).as_deref().map(str::trim) {
+                    None | Some("false") => false,
+                    Some("true") => true,
+                    Some(v) => return Err(format!("line {}: invalid override: {}", line, v).into()),
+                },
+            });
+        }
+        Ok(Self { rules })
+    }
+
+    /// 最初に当てはまった規則でタスクのプロジェクトを付け替え、付け替えたタスクを返す
+    ///
+    /// 休日は付け替える前のプロジェクトで判定したままなので、[`resolve_holidays`](crate::csv_parser::resolve_holidays) で判定し直す。
+    pub fn apply(&self, tasks: &mut [Task]) -> Vec<Reassignment> {
+        let known: Vec<_> = tasks.iter().filter_map(|t| t.project.clone()).collect();
+        let project = |r: &RemapRule| Project {
+            id: r.project_id.clone(),
+            name: r
+                .project_name
+                .clone()
+                .or_else(|| {
+                    known
+                        .iter()
+                        .find(|p| p.id == r.project_id)
+                        .map(|p| p.name.clone())
+                })
+                .unwrap_or_else(|| r.project_id.clone()),
+        };
+
+        let mut res = vec![];
+        for t in tasks.iter_mut() {
+            let rule = match self.rules.iter().find(|r| r.matches(t)) {
+                Some(r) => r,
+                None => continue,
+            };
+            let to = project(rule);
+            if t.project.as_ref() == Some(&to) {
+                continue;
+            }
+            res.push(Reassignment {
+                row: t.row,
+                task_id: t.id.clone(),
+                task_name: t.name.clone(),
+                from: t.project.replace(to.clone()),
+                to,
+                rule: rule.line,
+            });
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::task;
+
+    fn remapping(rules: &str) -> Remapping {
+        Remapping::from_csv(
+            format!(
+                "name,comment,period,project_id,project_name,override\n{}",
+                rules
+            )
+            .as_bytes(),
+        )
+        .unwrap()
+    }
+
+    fn projects(tasks: &[Task]) -> Vec<Option<(&str, &str)>> {
+        tasks
+            .iter()
+            .map(|t| t.project.as_ref().map(|p| (p.id.as_str(), p.name.as_str())))
+            .collect()
+    }
+
+    fn error(csv: &str) -> String {
+        Remapping::from_csv(csv.as_bytes()).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn errors_report_rule_lines() {
+        let header = "name,comment,period,project_id,project_name,override\n";
+        assert_eq!(
+            error(&format!("{}^a,,,100,,\n^b,,,,,\n", header)),
+            "line 3: empty project_id"
+        );
+        assert!(error(&format!("{}^a,,,100,,\n(,,,100,,\n", header)).starts_with("line 3: "));
+        assert!(error(&format!("{},,2020-13-01,100,,\n", header)).starts_with("line 2: "));
+        assert_eq!(
+            error(&format!("{}^a,,,100,,yes\n", header)),
+            "line 2: invalid override: yes"
+        );
+        // 改行を含むフィールドがあっても、規則の行番号はファイルの行番号
+        assert_eq!(
+            error(&format!("{}^a,\"複数\n行\",,100,,\n^b,,,,,\n", header)),
+            "line 4: empty project_id"
+        );
+    }
+
+    #[test]
+    fn rules_are_parsed_with_their_lines() {
+        let remapping = Remapping::from_csv(
+            "name,comment,period,project_id,project_name,override\n^メール,,,100,,\n,朝会,2020-05-01..,300,社内,true\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let rules: Vec<_> = remapping
+            .rules
+            .iter()
+            .map(|r| (r.line, r.project_id.as_str(), r.overwrite))
+            .collect();
+        assert_eq!(rules, vec![(2, "100", false), (3, "300", true)]);
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let mut tasks = vec![task("1", "図版 作成").build(), task("2", "図版").build()];
+        let res = remapping("^図版 ,,,100,本,\n^図版,,,200,雑誌,\n").apply(&mut tasks);
+        assert_eq!(
+            projects(&tasks),
+            vec![Some(("100", "本")), Some(("200", "雑誌"))]
+        );
+        let rules: Vec<_> = res.iter().map(|r| (r.task_id.as_str(), r.rule)).collect();
+        assert_eq!(rules, vec![("1", 2), ("2", 3)]);
+    }
+
+    #[test]
+    fn tasks_with_a_project_are_kept_unless_overridden() {
+        let mut tasks = vec![
+            task("1", "朝会").project("100", "本").build(),
+            task("2", "朝会").build(),
+        ];
+        remapping("^朝会,,,300,社内,\n").apply(&mut tasks);
+        assert_eq!(
+            projects(&tasks),
+            vec![Some(("100", "本")), Some(("300", "社内"))]
+        );
+
+        let res = remapping("^朝会,,,300,社内,true\n").apply(&mut tasks);
+        assert_eq!(projects(&tasks)[0], Some(("300", "社内")));
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].from.as_ref().map(|p| p.id.as_str()), Some("100"));
+    }
+
+    #[test]
+    fn project_name_is_looked_up_by_id() {
+        let mut tasks = vec![
+            task("1", "メール").build(),
+            task("2", "執筆").project("100", "本").build(),
+            task("3", "電話").build(),
+        ];
+        remapping("^メール,,,100,,\n^電話,,,999,,\n").apply(&mut tasks);
+        assert_eq!(
+            projects(&tasks),
+            vec![
+                Some(("100", "本")),
+                Some(("100", "本")),
+                Some(("999", "999"))
+            ]
+        );
+    }
+
+    #[test]
+    fn tasks_already_in_the_project_are_not_reassigned() {
+        let mut tasks = vec![
+            task("1", "執筆").project("100", "本").build(),
+            task("2", "執筆").build(),
+        ];
+        let res = remapping("^執筆,,,100,,true\n").apply(&mut tasks);
+        let ids: Vec<_> = res.iter().map(|r| r.task_id.as_str()).collect();
+        assert_eq!(ids, vec!["2"]);
+    }
+}
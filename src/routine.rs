@@ -0,0 +1,224 @@
+use crate::AnalysisResultTask;
+use chrono::NaiveDate;
+use itertools::Itertools;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// 同じ名前で繰り返し行ったタスク（正規化した名前が同じタスクを2回以上行ったもの）
+#[derive(Debug, Serialize)]
+pub struct Routine {
+    /// 正規化したタスク名
+    pub name: String,
+    /// 回数
+    pub count: i64,
+    /// 合計作業時間
+    pub total_work_time: i64,
+    /// 1回あたり作業時間平均
+    pub work_time_avg: f64,
+    /// 1回あたり作業時間中央
+    pub work_time_median: i64,
+    /// 1回ごとの作業時間の増減（最小二乗法の傾き、分/回）
+    pub trend: f64,
+    /// 最初に行った日
+    pub first_date: NaiveDate,
+    /// 最後に行った日
+    pub last_date: NaiveDate,
+}
+
+/// 定型タスクの判定条件
+#[derive(Debug, Clone)]
+pub struct RoutineRules {
+    /// 正規化した名前がこの日数以上に現れるタスクを定型とする
+    pub min_days: usize,
+    /// 日数によらず定型とするタスク名
+    pub routines: Vec<String>,
+    /// 日数によらず個別とするタスク名
+    pub one_offs: Vec<String>,
+}
+
+impl Default for RoutineRules {
+    fn default() -> Self {
+        Self {
+            min_days: 3,
+            routines: vec![],
+            one_offs: vec![],
+        }
+    }
+}
+
+/// タスクを定型・個別に分類する（名前の指定は正規化してから比べる）
+pub(crate) fn classify(tasks: &mut [AnalysisResultTask], rules: &RoutineRules) {
+    let routines: Vec<_> = rules.routines.iter().map(|n| normalize_name(n)).collect();
+    let one_offs: Vec<_> = rules.one_offs.iter().map(|n| normalize_name(n)).collect();
+    let mut days: HashMap<String, usize> = HashMap::new();
+    for (name, _) in tasks
+        .iter()
+        .map(|t| (normalize_name(&t.name), t.begin_time.date()))
+        .unique()
+    {
+        *days.entry(name).or_default() += 1;
+    }
+
+    for t in tasks.iter_mut() {
+        let name = normalize_name(&t.name);
+        t.routine = if one_offs.contains(&name) {
+            false
+        } else if routines.contains(&name) {
+            true
+        } else {
+            days.get(&name).copied().unwrap_or(0) >= rules.min_days
+        };
+    }
+}
+
+fn is_number_char(c: char) -> bool {
+    c.is_ascii_digit() || "/-.:#()[]（）年月日".contains(c)
+}
+
+/// 全角の数字・空白を半角にして空白をまとめ、末尾の番号や日付（`日報 4/27`、`朝会 #3`）を取り除く
+pub fn normalize_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| match c {
+            '０'..='９' => std::char::from_u32(c as u32 - '０' as u32 + '0' as u32).unwrap_or(c),
+            '／' => '/',
+            '＃' => '#',
+            _ => c,
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .join(" ");
+
+    let stripped = name.trim_end_matches(|c: char| is_number_char(c) || c.is_whitespace());
+    let suffix = &name[stripped.len()..];
+    if !stripped.is_empty() && suffix.chars().any(|c| c.is_ascii_digit()) {
+        stripped.to_string()
+    } else {
+        name
+    }
+}
+
+/// 正規化した名前ごとにタスクをまとめ、合計作業時間の長い順に並べる
+pub(crate) fn routines(tasks: &[AnalysisResultTask]) -> Vec<Routine> {
+    tasks
+        .iter()
+        .map(|t| (normalize_name(&t.name), t))
+        .sorted_by(|(a, t1), (b, t2)| a.cmp(b).then(t1.begin_time.cmp(&t2.begin_time)))
+        .group_by(|(n, _)| n.clone())
+        .into_iter()
+        .map(|(name, tasks)| (name, tasks.map(|(_, t)| t).collect::<Vec<_>>()))
+        .filter(|(_, tasks)| tasks.len() >= 2)
+        .map(|(name, tasks)| {
+            let times: Vec<_> = tasks.iter().map(|t| t.timespan).collect();
+            let total: i64 = times.iter().sum();
+            let n = times.len() as f64;
+            let avg = total as f64 / n;
+            let x_avg = (n - 1.0) / 2.0;
+            let trend = times
+                .iter()
+                .enumerate()
+                .map(|(i, t)| (i as f64 - x_avg) * (*t as f64 - avg))
+                .sum::<f64>()
+                / (0..times.len())
+                    .map(|i| (i as f64 - x_avg).powi(2))
+                    .sum::<f64>();
+
+            Routine {
+                name,
+                count: times.len() as i64,
+                total_work_time: total,
+                work_time_avg: avg,
+                work_time_median: times
+                    .iter()
+                    .sorted()
+                    .nth(times.len() / 2)
+                    .copied()
+                    .unwrap_or(0),
+                trend,
+                first_date: tasks[0].begin_time.date(),
+                last_date: tasks[tasks.len() - 1].begin_time.date(),
+            }
+        })
+        .sorted_by(|a, b| b.total_work_time.cmp(&a.total_work_time))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{at, task};
+
+    #[test]
+    fn names_are_normalized() {
+        let cases = [
+            // 日付
+            ("日報 4/27", "日報"),
+            ("日報 2020-04-27", "日報"),
+            ("週報（5月1日）", "週報"),
+            // 番号
+            ("朝会 #3", "朝会"),
+            ("レビュー(2)", "レビュー"),
+            // 全角の数字・空白
+            ("日報　４／２７", "日報"),
+            ("朝会 ＃１２", "朝会"),
+            // そのまま
+            ("第3回 打ち合わせ", "第3回 打ち合わせ"),
+            ("執筆 第3章", "執筆 第3章"),
+            ("朝会 (Zoom)", "朝会 (Zoom)"),
+            ("2020", "2020"),
+            ("  メール   処理 ", "メール 処理"),
+        ];
+        for (name, expected) in cases.iter() {
+            assert_eq!(normalize_name(name), *expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn tasks_recurring_on_enough_days_are_routines() {
+        let tasks = || {
+            vec![
+                task("1", "日報 6/1")
+                    .time(at(1, 18, 0), at(1, 18, 10))
+                    .done(),
+                task("2", "日報 6/2")
+                    .time(at(2, 18, 0), at(2, 18, 10))
+                    .done(),
+                task("3", "日報　６／３")
+                    .time(at(3, 18, 0), at(3, 18, 10))
+                    .done(),
+                // 同じ日に2回行っても1日と数える
+                task("4", "朝会").time(at(1, 9, 0), at(1, 9, 15)).done(),
+                task("5", "朝会").time(at(1, 13, 0), at(1, 13, 15)).done(),
+                task("6", "朝会").time(at(2, 9, 0), at(2, 9, 15)).done(),
+                task("7", "執筆 1章")
+                    .time(at(1, 10, 0), at(1, 12, 0))
+                    .done(),
+            ]
+        };
+        let routine = |rules: RoutineRules| {
+            let mut tasks = tasks();
+            classify(&mut tasks, &rules);
+            tasks.iter().map(|t| t.routine).collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            routine(RoutineRules::default()),
+            vec![true, true, true, false, false, false, false]
+        );
+        assert_eq!(
+            routine(RoutineRules {
+                min_days: 2,
+                ..RoutineRules::default()
+            }),
+            vec![true, true, true, true, true, true, false]
+        );
+        assert_eq!(
+            routine(RoutineRules {
+                routines: vec!["朝会 #1".to_string()],
+                one_offs: vec!["日報".to_string()],
+                ..RoutineRules::default()
+            }),
+            vec![false, false, false, true, true, true, false]
+        );
+    }
+}
@@ -0,0 +1,779 @@
+//! `analyze --format json`・`--format ndjson` の出力形式
+//!
+//! 分析結果の内部の構造とは切り離して、互換性を保つ形式で出力する。
+//! 形式を変えるときは [`SCHEMA_VERSION`] を上げて `schema/` のJSON Schemaを `tcc schema` で作り直す。
+//! 時間はすべて秒とISO 8601の期間（`PT1H30M`）の組、日時はISO 8601（タイムゾーンなしのローカル時刻）で出力する。
+
+use crate::{
+    analyzer::{BurnupAnalysisResult, DistributionAnalysisResult, FragmentationDay},
+    forecast::{RemainingAnalysisResult, RemainingTask},
+    grouping::{GroupNode, GroupTree},
+    holiday_work::HolidayWork,
+    routine::Routine,
+    working_hours::{WorkingDay, WorkingHoursAnalysisResult, WorkingWeek},
+    AnalysisResult, AnalysisResultTask, Project, Task, TasksAnalysisResult,
+};
+use chrono::{NaiveDate, NaiveDateTime};
+use schemars::{gen::SchemaGenerator, schema::RootSchema, JsonSchema};
+use serde::Serialize;
+use std::{error::Error, io::Write};
+
+/// 出力形式のバージョン（互換性のない変更でメジャー、項目の追加でマイナーを上げる）
+pub const SCHEMA_VERSION: &str = "1.0";
+
+/// 時間の長さ
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Span {
+    /// 秒
+    pub seconds: i64,
+    /// ISO 8601の期間（`PT1H30M`）
+    pub iso8601: String,
+}
+
+impl Span {
+    fn minutes(m: i64) -> Self {
+        Self::seconds(m * 60)
+    }
+
+    fn minutes_f64(m: f64) -> Self {
+        Self::seconds(if m.is_finite() {
+            (m * 60.0).round() as i64
+        } else {
+            0
+        })
+    }
+
+    fn seconds(seconds: i64) -> Self {
+        let (sign, s) = if seconds < 0 {
+            ("-", -seconds)
+        } else {
+            ("", seconds)
+        };
+        let (h, m, s) = (s / 3600, s % 3600 / 60, s % 60);
+        let mut iso = format!("{}PT", sign);
+        if h > 0 {
+            iso.push_str(&format!("{}H", h));
+        }
+        if m > 0 {
+            iso.push_str(&format!("{}M", m));
+        }
+        if s > 0 || (h == 0 && m == 0) {
+            iso.push_str(&format!("{}S", s));
+        }
+        Self {
+            seconds,
+            iso8601: iso,
+        }
+    }
+}
+
+/// `analyze --format json` の出力
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct AnalysisReport {
+    /// 出力形式のバージョン
+    pub schema_version: String,
+    pub project_name: String,
+    /// 1ページあたりの所要時間を求めるときのページ数などの値
+    pub value: Option<i64>,
+    /// 完了済みタスク全体の集計
+    pub summary: Metrics,
+    /// 観点ごとの分類
+    pub breakdowns: Vec<Breakdown>,
+    /// 同じ名前で繰り返し行ったタスク（`--routines` を指定したときのみ）
+    pub routines: Vec<RoutineSummary>,
+    /// 残タスクと完了予定
+    pub remaining: Remaining,
+    /// 日ごとの累積
+    pub burnup: Burnup,
+    /// 時間帯・曜日別の所要時間
+    pub distribution: Distribution,
+    /// 祝日・連休ごとの作業
+    pub holiday_work: Vec<HolidayPeriod>,
+    /// 所定内外・法定外・深夜・休日の作業時間
+    pub working_hours: WorkingHours,
+}
+
+/// 完了済みタスクの集計
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Metrics {
+    pub estimated_time: Span,
+    pub work_time: Span,
+    /// 所要時間/見積時間（見積時間がなければなし）
+    pub gap_ratio: Option<f64>,
+    /// 稼働日数
+    pub work_days: i64,
+    /// 1日あたり所要時間
+    pub work_time_per_day: DailyStats,
+    /// 1ページあたりの所要時間などの値あたりの所要時間
+    pub work_time_per_value: Option<Span>,
+    pub sessions: Sessions,
+    /// 日ごとのセッション・切り替え
+    pub days: Vec<FragmentationReport>,
+    pub tasks: Vec<TaskReport>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DailyStats {
+    pub mean: Span,
+    pub max: Span,
+    pub min: Span,
+    pub median: Span,
+    pub stddev: Span,
+}
+
+/// 短い間隔で続くタスクをまとめたセッション
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Sessions {
+    pub count: i64,
+    pub mean: Span,
+    pub max: Span,
+    /// セッション間でどのタスクもしていない時間の合計
+    pub idle_time: Span,
+    /// 同じ日の連続するタスク間で工程が切り替わった回数（分析するタスクだけで数える）
+    pub group_switches: i64,
+    /// 同じ日の連続するタスク間でプロジェクトが切り替わった回数（その日のすべてのタスクで数える）
+    pub project_switches: i64,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct FragmentationReport {
+    pub date: NaiveDate,
+    pub sessions: Sessions,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ProjectReport {
+    pub id: String,
+    pub name: String,
+}
+
+/// 完了済みタスク
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TaskReport {
+    pub id: String,
+    pub name: String,
+    /// 工程（タスク名の最初の単語）
+    pub group: Option<String>,
+    pub project: Option<ProjectReport>,
+    pub comment: Option<String>,
+    pub estimated_time: Option<Span>,
+    pub work_time: Span,
+    pub gap_ratio: Option<f64>,
+    pub begin: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub holiday: bool,
+    pub holiday_name: Option<String>,
+    /// 定型タスクかどうか
+    pub routine: bool,
+}
+
+/// 1つの観点（`month/group` のように入れ子にしたものを含む）での分類
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Breakdown {
+    /// 観点（`["month", "group"]`）
+    pub keys: Vec<String>,
+    pub nodes: Vec<BreakdownNode>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct BreakdownNode {
+    pub key: String,
+    pub metrics: Metrics,
+    /// 次の観点での分類（最後の観点では空）
+    pub children: Vec<BreakdownNode>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RoutineSummary {
+    /// 正規化したタスク名
+    pub name: String,
+    pub count: i64,
+    pub work_time: Span,
+    pub mean: Span,
+    pub median: Span,
+    /// 1回ごとの所要時間の増減（秒/回）
+    pub trend_seconds_per_occurrence: f64,
+    pub first_date: NaiveDate,
+    pub last_date: NaiveDate,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Remaining {
+    pub estimated_time: Span,
+    /// 見積時間を実績/予定の倍率で補正した予測所要時間
+    pub forecast_time: Span,
+    /// 完了済みタスクの所要時間と残タスクの予測所要時間の和
+    pub projected_total_work_time: Span,
+    pub remaining_work_days: Option<f64>,
+    pub estimated_completion_date: Option<NaiveDate>,
+    pub tasks: Vec<RemainingTaskReport>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RemainingTaskReport {
+    pub id: String,
+    pub name: String,
+    pub group: Option<String>,
+    pub project: Option<ProjectReport>,
+    pub comment: Option<String>,
+    pub estimated_time: Option<Span>,
+    /// 見積時間に掛けた倍率
+    pub gap_ratio: Option<f64>,
+    pub forecast_time: Option<Span>,
+    /// 開始済みで終了していない場合の開始時刻
+    pub begin: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Burnup {
+    /// 完了済みタスクと残タスクの見積時間の合計（残タスクがなければなし）
+    pub scope: Option<Span>,
+    pub days: Vec<BurnupDayReport>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct BurnupDayReport {
+    pub date: NaiveDate,
+    /// 累積所要時間
+    pub work_time: Span,
+    /// 完了済みタスクの累積見積時間
+    pub estimated_time: Span,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Distribution {
+    pub hours: Vec<HourReport>,
+    /// 月曜始まり
+    pub weekdays: Vec<WeekdayReport>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct HourReport {
+    /// 0〜23
+    pub hour: u32,
+    pub work_time: Span,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct WeekdayReport {
+    /// `Mon`〜`Sun`
+    pub weekday: String,
+    pub work_time: Span,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct HolidayPeriod {
+    /// 期間に含まれる休日の名称、または連休
+    pub name: String,
+    pub begin: NaiveDate,
+    pub end: NaiveDate,
+    pub work_days: i64,
+    pub work_time: Span,
+    pub tasks: Vec<TaskReport>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct WorkingHours {
+    pub totals: WorkingTime,
+    pub days: Vec<WorkingDayReport>,
+    pub weeks: Vec<WorkingWeekReport>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct WorkingTime {
+    pub work_time: Span,
+    /// 所定内
+    pub regular_time: Span,
+    /// 所定外
+    pub outside_time: Span,
+    /// 1日の法定労働時間を超えた分
+    pub overtime: Span,
+    /// 深夜（22:00〜5:00）
+    pub late_night_time: Span,
+    /// 休日
+    pub holiday_time: Span,
+    /// 1週間の法定労働時間を超えた分（1日の法定外・法定休日を除く）
+    pub weekly_overtime: Span,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct WorkingDayReport {
+    pub date: NaiveDate,
+    pub holiday: bool,
+    pub time: WorkingTime,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct WorkingWeekReport {
+    /// 週の初め（月曜日）
+    pub begin: NaiveDate,
+    pub time: WorkingTime,
+}
+
+/// `--format ndjson` の1行（完了済みタスク）
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TaskRecord {
+    pub id: String,
+    pub name: String,
+    /// 工程（タスク名の最初の単語）
+    pub group: Option<String>,
+    pub project_id: Option<String>,
+    pub project_name: Option<String>,
+    pub comment: Option<String>,
+    pub date: NaiveDate,
+    pub begin: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub estimated_seconds: Option<i64>,
+    pub work_seconds: i64,
+    /// 所要時間/見積時間
+    pub gap_ratio: Option<f64>,
+    pub holiday: bool,
+    pub holiday_name: Option<String>,
+    /// 定型タスクかどうか
+    pub routine: bool,
+}
+
+/// `analyze --format ndjson --raw`・`tasks` の1行（読み込んだままのタスク）
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RawTaskRecord {
+    pub id: String,
+    pub name: String,
+    /// CSVの行番号
+    pub row: u64,
+    pub project_id: Option<String>,
+    pub project_name: Option<String>,
+    pub comment: Option<String>,
+    /// 実行日
+    pub date: NaiveDate,
+    pub begin: Option<NaiveDateTime>,
+    pub end: Option<NaiveDateTime>,
+    pub estimated_seconds: Option<i64>,
+    /// 開始・終了していなければなし
+    pub work_seconds: Option<i64>,
+    pub holiday: bool,
+    pub holiday_name: Option<String>,
+}
+
+/// 1行目を項目名としたCSVで出力する
+pub fn write_csv_to<W: Write, T: Serialize>(
+    w: &mut W,
+    records: impl IntoIterator<Item = T>,
+) -> Result<(), Box<dyn Error>> {
+    let mut csv = csv::Writer::from_writer(w);
+    for r in records {
+        csv.serialize(r)?;
+    }
+    csv.flush()?;
+    Ok(())
+}
+
+/// 1行に1つずつJSONで出力する
+pub fn write_ndjson_to<W: Write, T: Serialize>(
+    w: &mut W,
+    records: impl IntoIterator<Item = T>,
+) -> Result<(), Box<dyn Error>> {
+    for r in records {
+        serde_json::to_writer(&mut *w, &r)?;
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+/// [`AnalysisReport`] のJSON Schema（NDJSONの1行の [`TaskRecord`]・[`RawTaskRecord`] は `definitions` に含める）
+pub fn json_schema() -> RootSchema {
+    let mut gen = SchemaGenerator::default();
+    gen.subschema_for::<TaskRecord>();
+    gen.subschema_for::<RawTaskRecord>();
+    gen.into_root_schema_for::<AnalysisReport>()
+}
+
+impl From<&AnalysisResult> for AnalysisReport {
+    fn from(v: &AnalysisResult) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION.into(),
+            project_name: v.project_name.clone(),
+            value: v.value,
+            summary: (&v.all).into(),
+            breakdowns: v.groups.iter().map(From::from).collect(),
+            routines: v.routines.iter().map(From::from).collect(),
+            remaining: (&v.remaining).into(),
+            burnup: (&v.burnup).into(),
+            distribution: (&v.distribution).into(),
+            holiday_work: v.holiday_work.iter().map(From::from).collect(),
+            working_hours: (&v.working_hours).into(),
+        }
+    }
+}
+
+impl From<&TasksAnalysisResult> for Metrics {
+    fn from(v: &TasksAnalysisResult) -> Self {
+        Self {
+            estimated_time: Span::minutes(v.total_estimated_time),
+            work_time: Span::minutes(v.total_work_time),
+            gap_ratio: v.total_time_gap_ratio,
+            work_days: v.work_days,
+            work_time_per_day: DailyStats {
+                mean: Span::minutes_f64(v.work_time_per_day),
+                max: Span::minutes(v.work_time_per_day_max),
+                min: Span::minutes(v.work_time_per_day_min),
+                median: Span::minutes(v.work_time_per_day_median),
+                stddev: Span::minutes_f64(v.work_time_per_day_deviation),
+            },
+            work_time_per_value: v.work_time_per_value.map(Span::minutes_f64),
+            sessions: Sessions {
+                count: v.sessions,
+                mean: Span::minutes_f64(v.session_time_avg),
+                max: Span::minutes(v.session_time_max),
+                idle_time: Span::minutes(v.idle_time),
+                group_switches: v.group_switches,
+                project_switches: v.project_switches,
+            },
+            days: v.fragmentation_per_days.iter().map(From::from).collect(),
+            tasks: v.tasks.iter().map(From::from).collect(),
+        }
+    }
+}
+
+impl From<&FragmentationDay> for FragmentationReport {
+    fn from(d: &FragmentationDay) -> Self {
+        Self {
+            date: d.date,
+            sessions: Sessions {
+                count: d.sessions,
+                mean: Span::minutes_f64(d.session_time_avg),
+                max: Span::minutes(d.session_time_max),
+                idle_time: Span::minutes(d.idle_time),
+                group_switches: d.group_switches,
+                project_switches: d.project_switches,
+            },
+        }
+    }
+}
+
+impl From<&Project> for ProjectReport {
+    fn from(p: &Project) -> Self {
+        Self {
+            id: p.id.clone(),
+            name: p.name.clone(),
+        }
+    }
+}
+
+impl From<&AnalysisResultTask> for TaskReport {
+    fn from(t: &AnalysisResultTask) -> Self {
+        Self {
+            id: t.id.clone(),
+            name: t.name.clone(),
+            group: t.group.clone(),
+            project: t.project.as_ref().map(From::from),
+            comment: t.comment.clone(),
+            estimated_time: t.estimated_time.map(Span::minutes),
+            work_time: Span::minutes(t.timespan),
+            gap_ratio: t.time_gap_ratio,
+            begin: t.begin_time,
+            end: t.end_time,
+            holiday: t.holiday,
+            holiday_name: t.holiday_name.clone(),
+            routine: t.routine,
+        }
+    }
+}
+
+impl From<&AnalysisResultTask> for TaskRecord {
+    fn from(t: &AnalysisResultTask) -> Self {
+        Self {
+            id: t.id.clone(),
+            name: t.name.clone(),
+            group: t.group.clone(),
+            project_id: t.project.as_ref().map(|p| p.id.clone()),
+            project_name: t.project.as_ref().map(|p| p.name.clone()),
+            comment: t.comment.clone(),
+            date: t.begin_time.date(),
+            begin: t.begin_time,
+            end: t.end_time,
+            estimated_seconds: t.estimated_time.map(|m| m * 60),
+            work_seconds: t.timespan * 60,
+            gap_ratio: t.time_gap_ratio,
+            holiday: t.holiday,
+            holiday_name: t.holiday_name.clone(),
+            routine: t.routine,
+        }
+    }
+}
+
+impl From<&Task> for RawTaskRecord {
+    fn from(t: &Task) -> Self {
+        Self {
+            id: t.id.clone(),
+            name: t.name.clone(),
+            row: t.row,
+            project_id: t.project.as_ref().map(|p| p.id.clone()),
+            project_name: t.project.as_ref().map(|p| p.name.clone()),
+            comment: t.comment.clone(),
+            date: t.date,
+            begin: t.begin_time,
+            end: t.end_time,
+            estimated_seconds: t.estimated_time.map(|d| d.num_seconds()),
+            work_seconds: t
+                .begin_time
+                .and_then(|b| t.end_time.map(|e| (e - b).num_seconds())),
+            holiday: t.holiday,
+            holiday_name: t.holiday_name.clone(),
+        }
+    }
+}
+
+impl From<&GroupTree> for Breakdown {
+    fn from(g: &GroupTree) -> Self {
+        Self {
+            keys: g.grouping.0.iter().map(|k| k.to_string()).collect(),
+            nodes: g.nodes.iter().map(From::from).collect(),
+        }
+    }
+}
+
+impl From<&GroupNode> for BreakdownNode {
+    fn from(n: &GroupNode) -> Self {
+        Self {
+            key: n.key.clone(),
+            metrics: (&n.result).into(),
+            children: n.children.iter().map(From::from).collect(),
+        }
+    }
+}
+
+impl From<&Routine> for RoutineSummary {
+    fn from(r: &Routine) -> Self {
+        Self {
+            name: r.name.clone(),
+            count: r.count,
+            work_time: Span::minutes(r.total_work_time),
+            mean: Span::minutes_f64(r.work_time_avg),
+            median: Span::minutes(r.work_time_median),
+            trend_seconds_per_occurrence: r.trend * 60.0,
+            first_date: r.first_date,
+            last_date: r.last_date,
+        }
+    }
+}
+
+impl From<&RemainingAnalysisResult> for Remaining {
+    fn from(r: &RemainingAnalysisResult) -> Self {
+        Self {
+            estimated_time: Span::minutes(r.total_estimated_time),
+            forecast_time: Span::minutes(r.total_forecast_time),
+            projected_total_work_time: Span::minutes(r.projected_total_work_time),
+            remaining_work_days: r.remaining_work_days,
+            estimated_completion_date: r.estimated_completion_date,
+            tasks: r.tasks.iter().map(From::from).collect(),
+        }
+    }
+}
+
+impl From<&RemainingTask> for RemainingTaskReport {
+    fn from(t: &RemainingTask) -> Self {
+        Self {
+            id: t.id.clone(),
+            name: t.name.clone(),
+            group: t.group.clone(),
+            project: t.project.as_ref().map(From::from),
+            comment: t.comment.clone(),
+            estimated_time: t.estimated_time.map(Span::minutes),
+            gap_ratio: t.time_gap_ratio,
+            forecast_time: t.forecast_time.map(Span::minutes),
+            begin: t.begin_time,
+        }
+    }
+}
+
+impl From<&BurnupAnalysisResult> for Burnup {
+    fn from(b: &BurnupAnalysisResult) -> Self {
+        Self {
+            scope: b.scope.map(Span::minutes),
+            days: b
+                .days
+                .iter()
+                .map(|d| BurnupDayReport {
+                    date: d.date,
+                    work_time: Span::minutes(d.work_time),
+                    estimated_time: Span::minutes(d.estimated_time),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&DistributionAnalysisResult> for Distribution {
+    fn from(d: &DistributionAnalysisResult) -> Self {
+        Self {
+            hours: d
+                .hour
+                .iter()
+                .map(|(hour, m)| HourReport {
+                    hour: *hour,
+                    work_time: Span::minutes(*m),
+                })
+                .collect(),
+            weekdays: d
+                .weekday
+                .iter()
+                .map(|(w, m)| WeekdayReport {
+                    weekday: format!("{:?}", w),
+                    work_time: Span::minutes(*m),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&HolidayWork> for HolidayPeriod {
+    fn from(h: &HolidayWork) -> Self {
+        Self {
+            name: h.name.clone(),
+            begin: h.begin,
+            end: h.end,
+            work_days: h.work_days,
+            work_time: Span::minutes(h.work_time),
+            tasks: h.tasks.iter().map(From::from).collect(),
+        }
+    }
+}
+
+impl From<&WorkingHoursAnalysisResult> for WorkingHours {
+    fn from(w: &WorkingHoursAnalysisResult) -> Self {
+        Self {
+            totals: WorkingTime {
+                work_time: Span::minutes(w.weeks.iter().map(|w| w.work_time).sum()),
+                regular_time: Span::minutes(w.regular_time),
+                outside_time: Span::minutes(w.outside_time),
+                overtime: Span::minutes(w.overtime),
+                late_night_time: Span::minutes(w.late_night_time),
+                holiday_time: Span::minutes(w.holiday_time),
+                weekly_overtime: Span::minutes(w.weekly_overtime),
+            },
+            days: w.days.iter().map(From::from).collect(),
+            weeks: w.weeks.iter().map(From::from).collect(),
+        }
+    }
+}
+
+impl From<&WorkingDay> for WorkingDayReport {
+    fn from(d: &WorkingDay) -> Self {
+        Self {
+            date: d.date,
+            holiday: d.holiday,
+            time: WorkingTime {
+                work_time: Span::minutes(d.work_time),
+                regular_time: Span::minutes(d.regular_time),
+                outside_time: Span::minutes(d.outside_time),
+                overtime: Span::minutes(d.overtime),
+                late_night_time: Span::minutes(d.late_night_time),
+                holiday_time: Span::minutes(if d.holiday { d.work_time } else { 0 }),
+                weekly_overtime: Span::minutes(0),
+            },
+        }
+    }
+}
+
+impl From<&WorkingWeek> for WorkingWeekReport {
+    fn from(w: &WorkingWeek) -> Self {
+        Self {
+            begin: w.begin,
+            time: WorkingTime {
+                work_time: Span::minutes(w.work_time),
+                regular_time: Span::minutes(w.regular_time),
+                outside_time: Span::minutes(w.outside_time),
+                overtime: Span::minutes(w.overtime),
+                late_night_time: Span::minutes(w.late_night_time),
+                holiday_time: Span::minutes(w.holiday_time),
+                weekly_overtime: Span::minutes(w.weekly_overtime),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{at, task};
+    use serde_json::{json, Value};
+
+    fn lines(out: Vec<u8>) -> Vec<Value> {
+        String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn checked_in_schema_is_up_to_date() {
+        assert_eq!(
+            serde_json::to_string_pretty(&json_schema()).unwrap(),
+            include_str!("../schema/analysis.v1.schema.json").trim_end(),
+            "schema/analysis.v1.schema.json を `tcc schema` の出力で更新してください"
+        );
+    }
+
+    #[test]
+    fn spans_are_iso8601_durations() {
+        let cases = [
+            (Span::minutes(0), 0, "PT0S"),
+            (Span::minutes(45), 2700, "PT45M"),
+            (Span::minutes(90), 5400, "PT1H30M"),
+            (Span::minutes(120), 7200, "PT2H"),
+            (Span::minutes(-90), -5400, "-PT1H30M"),
+            (Span::minutes_f64(1.5), 90, "PT1M30S"),
+            (Span::minutes_f64(f64::NAN), 0, "PT0S"),
+        ];
+        for (span, seconds, iso) in cases.iter() {
+            assert_eq!((span.seconds, span.iso8601.as_str()), (*seconds, *iso));
+        }
+    }
+
+    #[test]
+    fn ndjson_has_one_task_per_line() {
+        let tasks = [
+            task("1", "執筆 1章")
+                .project("a", "本")
+                .estimated(40)
+                .time(at(1, 9, 0), at(1, 10, 0))
+                .done(),
+            task("2", "メール").time(at(1, 10, 0), at(1, 10, 5)).done(),
+        ];
+        let mut out = vec![];
+        write_ndjson_to(&mut out, tasks.iter().map(TaskRecord::from)).unwrap();
+        let lines = lines(out);
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["group"], json!("執筆"));
+        assert_eq!(lines[0]["project_id"], json!("a"));
+        assert_eq!(lines[0]["begin"], json!("2020-06-01T09:00:00"));
+        assert_eq!(lines[0]["estimated_seconds"], json!(2400));
+        assert_eq!(lines[0]["work_seconds"], json!(3600));
+        assert_eq!(lines[0]["gap_ratio"], json!(1.5));
+        assert_eq!(lines[1]["project_id"], Value::Null);
+        assert_eq!(lines[1]["estimated_seconds"], Value::Null);
+    }
+
+    #[test]
+    fn raw_ndjson_keeps_unfinished_tasks() {
+        let tasks = [
+            task("1", "執筆 1章")
+                .time(at(1, 9, 0), at(1, 10, 0))
+                .build(),
+            task("2", "執筆 2章").started(at(2, 9, 0)).build(),
+            task("3", "執筆 3章").estimated(30).build(),
+        ];
+        let mut out = vec![];
+        write_ndjson_to(&mut out, tasks.iter().map(RawTaskRecord::from)).unwrap();
+        let lines = lines(out);
+
+        let work: Vec<_> = lines.iter().map(|l| l["work_seconds"].clone()).collect();
+        assert_eq!(work, vec![json!(3600), Value::Null, Value::Null]);
+        assert_eq!(lines[1]["begin"], json!("2020-06-02T09:00:00"));
+        assert_eq!(lines[1]["end"], Value::Null);
+        assert_eq!(lines[2]["row"], json!(3));
+        assert_eq!(lines[2]["estimated_seconds"], json!(1800));
+    }
+}
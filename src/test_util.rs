@@ -0,0 +1,89 @@
+//! テストで使うタスク
+
+use crate::{holiday::HolidayCalendar, AnalysisResult, AnalysisResultTask, Project, Query, Task};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+
+/// 祝日のないカレンダーで分析する（組み込みの祝日はユーザーデータで変わるので使わない）
+pub(crate) fn analyze(
+    tasks: Vec<Task>,
+    project_id: &str,
+    value: Option<i64>,
+) -> Option<AnalysisResult> {
+    let calendar = HolidayCalendar::default();
+    let result = Query::new()
+        .project(project_id)
+        .value(value)
+        .calendar(&calendar)
+        .analyze(tasks);
+    result
+}
+
+/// 2020年6月の日付（2020-06-01 は月曜日）
+pub(crate) fn day(d: u32) -> NaiveDate {
+    NaiveDate::from_ymd(2020, 6, d)
+}
+
+/// 2020年6月の日時
+pub(crate) fn at(d: u32, h: u32, m: u32) -> NaiveDateTime {
+    day(d).and_hms(h, m, 0)
+}
+
+/// タスクを組み立てる（既定はプロジェクト・見積時間・開始時刻のない2020-06-01のタスク）
+pub(crate) struct TaskBuilder(Task);
+
+pub(crate) fn task(id: &str, name: &str) -> TaskBuilder {
+    TaskBuilder(Task {
+        id: id.to_string(),
+        name: name.to_string(),
+        row: id.parse().unwrap_or(0),
+        date: day(1),
+        estimated_time: None,
+        begin_time: None,
+        end_time: None,
+        comment: None,
+        project: None,
+        holiday: false,
+        holiday_name: None,
+    })
+}
+
+impl TaskBuilder {
+    /// 開始・終了した日時（実行日は開始した日）
+    pub(crate) fn time(mut self, begin: NaiveDateTime, end: NaiveDateTime) -> Self {
+        self.0.date = begin.date();
+        self.0.begin_time = Some(begin);
+        self.0.end_time = Some(end);
+        self
+    }
+
+    /// 開始して終了していない
+    pub(crate) fn started(mut self, begin: NaiveDateTime) -> Self {
+        self.0.date = begin.date();
+        self.0.begin_time = Some(begin);
+        self.0.end_time = None;
+        self
+    }
+
+    /// 見積時間（分）
+    pub(crate) fn estimated(mut self, minutes: i64) -> Self {
+        self.0.estimated_time = Some(Duration::minutes(minutes));
+        self
+    }
+
+    pub(crate) fn project(mut self, id: &str, name: &str) -> Self {
+        self.0.project = Some(Project {
+            id: id.to_string(),
+            name: name.to_string(),
+        });
+        self
+    }
+
+    pub(crate) fn build(self) -> Task {
+        self.0
+    }
+
+    /// 完了済みタスクとして分析した結果のタスク
+    pub(crate) fn done(self) -> AnalysisResultTask {
+        self.0.into()
+    }
+}
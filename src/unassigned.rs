@@ -0,0 +1,218 @@
+use crate::{routine::normalize_name, Project, Task};
+use itertools::Itertools;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// この類似度以上のタスク名があればプロジェクトを推定する
+const MIN_SIMILARITY: f64 = 0.5;
+
+/// プロジェクトのないタスクの集計
+#[derive(Debug, Serialize)]
+pub struct UnassignedResult {
+    /// 完了済みタスク全体の所要時間（分）
+    pub total_work_time: i64,
+    /// プロジェクトのない完了済みタスクの所要時間（分）
+    pub work_time: i64,
+    /// 全体の所要時間に占める割合
+    pub share: Option<f64>,
+    /// プロジェクトのないタスク数（終わっていないタスクを含む）
+    pub tasks: i64,
+    /// 正規化したタスク名ごとの集計（所要時間の長い順）
+    pub names: Vec<UnassignedName>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UnassignedName {
+    /// 正規化したタスク名
+    pub name: String,
+    pub count: i64,
+    /// 所要時間（分）
+    pub work_time: i64,
+    /// 名前の似ているプロジェクトのあるタスクから推定したプロジェクト
+    pub suggestion: Option<Suggestion>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Suggestion {
+    pub project: Project,
+    /// タスク名の類似度（0〜1）
+    pub similarity: f64,
+    /// 最も似ていたプロジェクトのあるタスクの名前（正規化したもの）
+    pub similar_task: String,
+}
+
+/// プロジェクトのないタスクを集計し、プロジェクトのあるタスクとの名前の類似度からプロジェクトを推定する
+pub fn unassigned(tasks: &[Task]) -> UnassignedResult {
+    let work_time = |t: &Task| {
+        t.begin_time
+            .and_then(|b| t.end_time.map(|e| (e - b).num_minutes()))
+    };
+    let total_work_time: i64 = tasks.iter().filter_map(work_time).sum();
+
+    // 同じ名前が複数のプロジェクトにあれば、タスク数の多いプロジェクトを使う
+    let mut assigned: HashMap<String, HashMap<&Project, usize>> = HashMap::new();
+    for t in tasks {
+        if let Some(p) = &t.project {
+            *assigned
+                .entry(normalize_name(&t.name))
+                .or_default()
+                .entry(p)
+                .or_default() += 1;
+        }
+    }
+    let assigned: Vec<_> = assigned
+        .into_iter()
+        .map(|(name, projects)| {
+            let project = projects
+                .into_iter()
+                .max_by(|(p1, n1), (p2, n2)| n1.cmp(n2).then(p2.id.cmp(&p1.id)))
+                .map(|(p, _)| p)
+                .unwrap();
+            (bigrams(&name), name, project)
+        })
+        .collect();
+
+    let unassigned: Vec<_> = tasks.iter().filter(|t| t.project.is_none()).collect();
+    let names = unassigned
+        .iter()
+        .map(|t| (normalize_name(&t.name), work_time(t)))
+        .into_group_map()
+        .into_iter()
+        .map(|(name, times)| {
+            let grams = bigrams(&name);
+            let suggestion = assigned
+                .iter()
+                .map(|(g, n, p)| (similarity(&grams, g), n, p))
+                .filter(|(s, _, _)| *s >= MIN_SIMILARITY)
+                .max_by(|(s1, n1, _), (s2, n2, _)| {
+                    s1.partial_cmp(s2)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                        .then(n2.cmp(n1))
+                })
+                .map(|(similarity, n, p)| Suggestion {
+                    project: (*p).clone(),
+                    similarity,
+                    similar_task: n.clone(),
+                });
+            UnassignedName {
+                name,
+                count: times.len() as i64,
+                work_time: times.iter().flatten().sum(),
+                suggestion,
+            }
+        })
+        .sorted_by(|a, b| b.work_time.cmp(&a.work_time).then(a.name.cmp(&b.name)))
+        .collect();
+
+    let work_time: i64 = unassigned.iter().filter_map(|t| work_time(t)).sum();
+    UnassignedResult {
+        total_work_time,
+        work_time,
+        share: if total_work_time == 0 {
+            None
+        } else {
+            Some(work_time as f64 / total_work_time as f64)
+        },
+        tasks: unassigned.len() as i64,
+        names,
+    }
+}
+
+/// 空白を除いた文字の2-gram（1文字の名前はその文字）
+fn bigrams(name: &str) -> HashSet<String> {
+    let chars: Vec<_> = name.chars().filter(|c| !c.is_whitespace()).collect();
+    if chars.len() < 2 {
+        return chars.iter().map(|c| c.to_string()).collect();
+    }
+    chars.windows(2).map(|w| w.iter().collect()).collect()
+}
+
+/// Dice係数
+fn similarity(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    if a.is_empty() && b.is_empty() {
+        return 0.0;
+    }
+    2.0 * a.intersection(b).count() as f64 / (a.len() + b.len()) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{at, task};
+
+    #[test]
+    fn projects_are_suggested_from_similar_names() {
+        let tasks = vec![
+            task("1", "図版 作成")
+                .project("200", "図版")
+                .time(at(1, 9, 0), at(1, 10, 0))
+                .build(),
+            task("2", "原稿 執筆")
+                .project("100", "本")
+                .time(at(1, 10, 0), at(1, 11, 0))
+                .build(),
+            task("3", "原稿 執筆")
+                .project("100", "本")
+                .time(at(2, 10, 0), at(2, 11, 0))
+                .build(),
+            task("4", "原稿 執筆")
+                .project("300", "雑誌")
+                .time(at(3, 10, 0), at(3, 11, 0))
+                .build(),
+            task("5", "図版作成 6/1")
+                .time(at(1, 13, 0), at(1, 13, 40))
+                .build(),
+            task("6", "図版作成 6/2")
+                .time(at(2, 13, 0), at(2, 13, 20))
+                .build(),
+            task("7", "原稿の執筆")
+                .time(at(3, 13, 0), at(3, 13, 30))
+                .build(),
+            task("8", "メール")
+                .time(at(3, 14, 0), at(3, 14, 30))
+                .build(),
+            task("9", "メール").build(),
+        ];
+        let res = unassigned(&tasks);
+
+        assert_eq!(
+            (res.total_work_time, res.work_time, res.tasks),
+            (360, 120, 5)
+        );
+        assert_eq!(res.share, Some(120.0 / 360.0));
+
+        let names: Vec<_> = res
+            .names
+            .iter()
+            .map(|n| {
+                (
+                    n.name.as_str(),
+                    n.count,
+                    n.work_time,
+                    n.suggestion.as_ref().map(|s| s.project.id.as_str()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("図版作成", 2, 60, Some("200")),
+                ("メール", 2, 30, None),
+                ("原稿の執筆", 1, 30, Some("100")),
+            ]
+        );
+        let suggestion = res.names[0].suggestion.as_ref().unwrap();
+        assert_eq!(suggestion.similarity, 1.0);
+        assert_eq!(suggestion.similar_task, "図版 作成");
+    }
+
+    #[test]
+    fn similarity_is_the_dice_coefficient_of_bigrams() {
+        let s = |a: &str, b: &str| similarity(&bigrams(a), &bigrams(b));
+        assert_eq!(s("原稿 執筆", "原稿執筆"), 1.0);
+        // {原稿, 稿の, の執, 執筆} と {原稿, 稿執, 執筆}
+        assert_eq!(s("原稿の執筆", "原稿 執筆"), 4.0 / 7.0);
+        assert_eq!(s("メール", "図版"), 0.0);
+        assert_eq!(s("", ""), 0.0);
+    }
+}
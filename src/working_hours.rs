@@ -0,0 +1,291 @@
+use crate::{holiday::HolidayProvider, AnalysisResultTask};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use itertools::Itertools;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// 所定労働時間の設定
+#[derive(Debug, Clone, Copy)]
+pub struct WorkingHours {
+    /// 始業時刻
+    pub begin: NaiveTime,
+    /// 終業時刻
+    pub end: NaiveTime,
+    /// 休憩時間（分）
+    pub break_time: i64,
+    /// 1日の法定労働時間（分）
+    pub daily_limit: i64,
+    /// 1週間の法定労働時間（分）
+    pub weekly_limit: i64,
+    /// 法定休日の曜日（この曜日の作業時間は1日・1週間の法定外に数えない）
+    pub rest_day: Weekday,
+}
+
+impl Default for WorkingHours {
+    fn default() -> Self {
+        Self {
+            begin: NaiveTime::from_hms(9, 0, 0),
+            end: NaiveTime::from_hms(18, 0, 0),
+            break_time: 60,
+            daily_limit: 8 * 60,
+            weekly_limit: 40 * 60,
+            rest_day: Weekday::Sun,
+        }
+    }
+}
+
+impl WorkingHours {
+    /// 始業から終業までのうち休憩を除いた所定労働時間（分）
+    fn regular_limit(&self) -> i64 {
+        ((self.end - self.begin).num_minutes() - self.break_time).max(0)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct WorkingHoursAnalysisResult {
+    /// 所定内の作業時間
+    pub regular_time: i64,
+    /// 所定外の作業時間
+    pub outside_time: i64,
+    /// 1日の法定労働時間を超えた作業時間（法定休日を除く）
+    pub overtime: i64,
+    /// 深夜（22:00〜5:00）の作業時間
+    pub late_night_time: i64,
+    /// 休日の作業時間
+    pub holiday_time: i64,
+    /// 1週間の法定労働時間を超えた作業時間（1日の法定外・法定休日の作業時間を除く）
+    pub weekly_overtime: i64,
+    pub days: Vec<WorkingDay>,
+    pub weeks: Vec<WorkingWeek>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct WorkingDay {
+    pub date: NaiveDate,
+    pub holiday: bool,
+    pub work_time: i64,
+    pub regular_time: i64,
+    pub outside_time: i64,
+    pub overtime: i64,
+    pub late_night_time: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WorkingWeek {
+    /// 週の初め（月曜日）
+    pub begin: NaiveDate,
+    pub work_time: i64,
+    pub regular_time: i64,
+    pub outside_time: i64,
+    pub overtime: i64,
+    pub late_night_time: i64,
+    pub holiday_time: i64,
+    pub weekly_overtime: i64,
+}
+
+/// 2つの区間が重なる時間（分）
+fn overlap(a: (NaiveDateTime, NaiveDateTime), b: (NaiveDateTime, NaiveDateTime)) -> i64 {
+    (a.1.min(b.1) - a.0.max(b.0)).num_minutes().max(0)
+}
+
+/// 重なっているタスクの時間を1つにまとめ、日ごとに分けた区間を返す
+fn daily_intervals(tasks: &[AnalysisResultTask]) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+    let mut merged: Vec<(NaiveDateTime, NaiveDateTime)> = vec![];
+    for (b, e) in tasks.iter().map(|t| (t.begin_time, t.end_time)).sorted() {
+        match merged.last_mut() {
+            Some(last) if b <= last.1 => last.1 = last.1.max(e),
+            _ => merged.push((b, e)),
+        }
+    }
+
+    let mut res = vec![];
+    for (mut b, e) in merged {
+        while b < e {
+            let midnight = b.date().succ().and_hms(0, 0, 0);
+            res.push((b, e.min(midnight)));
+            b = midnight;
+        }
+    }
+    res
+}
+
+/// 日ごと・週ごとに所定内外・法定外・深夜・休日の作業時間を求める
+///
+/// 重なっているタスクの時間は一度だけ数え、日をまたぐタスクは日ごとに分ける。
+pub(crate) fn working_hours(
+    tasks: &[AnalysisResultTask],
+    project_id: Option<&str>,
+    config: &WorkingHours,
+    calendar: &dyn HolidayProvider,
+) -> WorkingHoursAnalysisResult {
+    // 日付ごとの（作業時間、始業〜終業の間の作業時間、深夜の作業時間）
+    let mut minutes: BTreeMap<NaiveDate, (i64, i64, i64)> = BTreeMap::new();
+    for (b, e) in daily_intervals(tasks) {
+        let date = b.date();
+        let at = |h| date.and_hms(h, 0, 0);
+        let m = minutes.entry(date).or_default();
+        m.0 += (e - b).num_minutes();
+        m.1 += overlap(
+            (b, e),
+            (date.and_time(config.begin), date.and_time(config.end)),
+        );
+        // 深夜（0:00〜5:00、22:00〜24:00）
+        m.2 += overlap((b, e), (at(0), at(5)))
+            + overlap((b, e), (at(22), date.succ().and_hms(0, 0, 0)));
+    }
+
+    let days: Vec<_> = minutes
+        .into_iter()
+        .map(|(date, (work_time, inside, late_night_time))| {
+            let holiday = matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+                || calendar.holiday(&date, project_id).is_some();
+            let regular_time = if holiday {
+                0
+            } else {
+                inside.min(config.regular_limit())
+            };
+            WorkingDay {
+                date,
+                holiday,
+                work_time,
+                regular_time,
+                outside_time: work_time - regular_time,
+                // 法定休日の作業は1日・1週間の法定労働時間に数えない
+                overtime: if date.weekday() == config.rest_day {
+                    0
+                } else {
+                    (work_time - config.daily_limit).max(0)
+                },
+                late_night_time,
+            }
+        })
+        .collect();
+
+    let weeks: Vec<_> = days
+        .iter()
+        .group_by(|d| d.date - Duration::days(d.date.weekday().num_days_from_monday() as i64))
+        .into_iter()
+        .map(|(begin, days)| {
+            let days: Vec<_> = days.collect();
+            let counted_time: i64 = days
+                .iter()
+                .filter(|d| d.date.weekday() != config.rest_day)
+                .map(|d| d.work_time)
+                .sum();
+            let overtime: i64 = days.iter().map(|d| d.overtime).sum();
+            WorkingWeek {
+                begin,
+                work_time: days.iter().map(|d| d.work_time).sum(),
+                regular_time: days.iter().map(|d| d.regular_time).sum(),
+                outside_time: days.iter().map(|d| d.outside_time).sum(),
+                overtime,
+                late_night_time: days.iter().map(|d| d.late_night_time).sum(),
+                holiday_time: days.iter().filter(|d| d.holiday).map(|d| d.work_time).sum(),
+                // 法定休日を除いた週の作業時間のうち、1日の法定外として数えた分を除いて、週の法定労働時間を超えた分
+                weekly_overtime: (counted_time - overtime - config.weekly_limit).max(0),
+            }
+        })
+        .collect();
+
+    WorkingHoursAnalysisResult {
+        regular_time: weeks.iter().map(|w| w.regular_time).sum(),
+        outside_time: weeks.iter().map(|w| w.outside_time).sum(),
+        overtime: weeks.iter().map(|w| w.overtime).sum(),
+        late_night_time: weeks.iter().map(|w| w.late_night_time).sum(),
+        holiday_time: weeks.iter().map(|w| w.holiday_time).sum(),
+        weekly_overtime: weeks.iter().map(|w| w.weekly_overtime).sum(),
+        days,
+        weeks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        holiday::HolidayCalendar,
+        test_util::{at, day, task},
+    };
+
+    #[test]
+    fn overlapping_tasks_are_counted_once() {
+        let tasks = [
+            task("1", "1").time(at(1, 17, 0), at(1, 23, 0)).done(),
+            task("2", "2").time(at(1, 22, 0), at(2, 1, 0)).done(),
+        ];
+        let res = working_hours(
+            &tasks,
+            None,
+            &WorkingHours::default(),
+            &HolidayCalendar::default(),
+        );
+        let days: Vec<_> = res
+            .days
+            .iter()
+            .map(|d| (d.date, d.work_time, d.regular_time, d.late_night_time))
+            .collect();
+        assert_eq!(days, vec![(day(1), 420, 60, 120), (day(2), 60, 0, 60)]);
+    }
+
+    #[test]
+    fn daily_overtime_applies_except_on_rest_day() {
+        // 6日が土曜日、7日が日曜日（法定休日）
+        let tasks: Vec<_> = [3, 6, 7]
+            .iter()
+            .map(|d| {
+                task(&d.to_string(), "作業")
+                    .time(at(*d, 8, 0), at(*d, 20, 0))
+                    .done()
+            })
+            .collect();
+        let res = working_hours(
+            &tasks,
+            None,
+            &WorkingHours::default(),
+            &HolidayCalendar::default(),
+        );
+        let days: Vec<_> = res
+            .days
+            .iter()
+            .map(|d| (d.date, d.holiday, d.overtime))
+            .collect();
+        assert_eq!(
+            days,
+            vec![(day(3), false, 240), (day(6), true, 240), (day(7), true, 0)]
+        );
+        assert_eq!(res.weekly_overtime, 0);
+
+        let res = working_hours(
+            &tasks,
+            None,
+            &WorkingHours {
+                rest_day: Weekday::Sat,
+                ..WorkingHours::default()
+            },
+            &HolidayCalendar::default(),
+        );
+        let overtime: Vec<_> = res.days.iter().map(|d| d.overtime).collect();
+        assert_eq!(overtime, vec![240, 0, 240]);
+    }
+
+    #[test]
+    fn weekly_overtime_counts_saturday_work() {
+        // 月曜日から土曜日まで8時間ずつ
+        let tasks: Vec<_> = (1..=6)
+            .map(|d| {
+                task(&d.to_string(), "作業")
+                    .time(at(d, 9, 0), at(d, 17, 0))
+                    .done()
+            })
+            .collect();
+        let res = working_hours(
+            &tasks,
+            None,
+            &WorkingHours::default(),
+            &HolidayCalendar::default(),
+        );
+        assert_eq!(res.overtime, 0);
+        assert_eq!(res.holiday_time, 8 * 60);
+        assert_eq!(res.weekly_overtime, 8 * 60);
+    }
+}